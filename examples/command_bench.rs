@@ -0,0 +1,96 @@
+use egui::{vec2, Color32, Pos2, RawInput, Rect};
+use wasm_bindgen::JsCast;
+use web_sys::HtmlCanvasElement;
+
+/// frames painted per mode.
+const FRAMES: usize = 200;
+
+/// paints egui demo windows with direct canvas calls and with command buffer, logs both.
+pub fn main() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas: HtmlCanvasElement = document.create_element("canvas").unwrap().unchecked_into();
+    let style = canvas.style();
+    style.set_property("width", "1280px").unwrap();
+    style.set_property("height", "800px").unwrap();
+    document.body().unwrap().append_child(&canvas).unwrap();
+    wasm_bindgen_futures::spawn_local(run(canvas));
+}
+
+async fn run(canvas: HtmlCanvasElement) {
+    let mut renderer = egui_canvas::Renderer::new_with_canvas(&canvas).unwrap();
+    let context = egui::Context::default();
+    let mut demo = egui_demo_lib::DemoWindows::default();
+    let input = RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, vec2(1280.0, 800.0))),
+        ..Default::default()
+    };
+    // layout settles after a few frames.
+    let mut output = context.run(input.clone(), |context| demo.ui(context));
+    for _ in 0..3 {
+        let next = context.run(input.clone(), |context| demo.ui(context));
+        output.textures_delta.append(next.textures_delta);
+        output.shapes = next.shapes;
+    }
+    let shapes = output.shapes;
+    renderer.clear(&Color32::BLACK);
+    renderer.paint_and_update_texture(&shapes, output.textures_delta);
+    while renderer.pending_uploads() > 0 {
+        sleep(10).await;
+    }
+
+    let performance = web_sys::window().unwrap().performance().unwrap();
+    let mut frame_ms = [0.0; 2];
+    let mut direct_calls = [0; 2];
+    for (mode, buffered) in [false, true].into_iter().enumerate() {
+        renderer.set_command_buffer(buffered);
+        // warm up text cache and tinted copies.
+        renderer.clear(&Color32::BLACK);
+        renderer.paint_and_update_texture(&shapes, Default::default());
+        let start = performance.now();
+        for _ in 0..FRAMES {
+            renderer.clear(&Color32::BLACK);
+            renderer.paint_and_update_texture(&shapes, Default::default());
+        }
+        frame_ms[mode] = (performance.now() - start) / FRAMES as f64;
+        direct_calls[mode] = renderer.direct_calls();
+        // let browser rasterize before next mode.
+        sleep(100).await;
+    }
+    let stats = renderer.command_stats().unwrap();
+    log::info!(
+        "direct: {:.3} ms per frame, {} js calls",
+        frame_ms[0],
+        direct_calls[0]
+    );
+    log::info!(
+        "command buffer: {:.3} ms per frame, {} canvas calls run by about {} js calls, {} direct",
+        frame_ms[1],
+        stats.ops,
+        stats.calls,
+        direct_calls[1]
+    );
+}
+
+async fn sleep(ms: i32) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms)
+            .unwrap();
+    });
+    wasm_bindgen_futures::JsFuture::from(promise).await.unwrap();
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    pub fn start() {
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        use log::Level;
+        console_log::init_with_level(Level::Info).expect("failed to init logger");
+
+        crate::main()
+    }
+}
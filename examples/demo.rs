@@ -1,6 +1,6 @@
 use instant::Instant;
 
-use egui::{Color32, FontData, FullOutput};
+use egui::{Color32, FontData};
 use egui_winit_platform::{Platform, PlatformDescriptor};
 use epi::*;
 use winit::dpi::LogicalPosition;
@@ -75,20 +75,15 @@ fn run(event_loop: EventLoop<()>, window: Window) {
                 demo_app.update(&platform.context(), &mut frame);
 
                 // End the UI frame. We could now handle the output and draw the UI with the backend.
-                let FullOutput {
-                    platform_output,
-                    needs_repaint,
-                    textures_delta,
-                    shapes,
-                } = platform.end_frame(Some(&window));
-                if let Some(pos) = platform_output.text_cursor_pos {
-                    window.set_ime_position(LogicalPosition::new(pos.x, pos.y));
-                }
+                let full_output = platform.end_frame(Some(&window));
                 let frame_time = (Instant::now() - egui_start).as_secs_f64() as f32;
                 previous_frame_time = Some(frame_time);
 
-                renderer.clear(&Color32::BLACK);
-                renderer.paint_and_update_texture(&shapes, textures_delta);
+                let (platform_output, needs_repaint) =
+                    renderer.render_full_output(&Color32::BLACK, full_output);
+                if let Some(pos) = platform_output.text_cursor_pos {
+                    window.set_ime_position(LogicalPosition::new(pos.x, pos.y));
+                }
                 if needs_repaint {
                     window.request_redraw();
                 }
@@ -0,0 +1,32 @@
+use wasm_bindgen::JsCast;
+use web_sys::HtmlCanvasElement;
+
+/// egui demo app driven by egui_canvas::runner. no winit.
+pub fn main() {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas: HtmlCanvasElement = document.create_element("canvas").unwrap().unchecked_into();
+    let style = canvas.style();
+    style.set_property("width", "100vw").unwrap();
+    style.set_property("height", "100vh").unwrap();
+    style.set_property("display", "block").unwrap();
+    document.body().unwrap().append_child(&canvas).unwrap();
+
+    let runner =
+        egui_canvas::runner::AppRunner::new(&canvas, Box::new(egui_demo_lib::WrapApp::default()))
+            .unwrap();
+    egui_canvas::runner::start(runner);
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use wasm_bindgen::prelude::wasm_bindgen;
+
+    #[wasm_bindgen]
+    pub fn start() {
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        use log::Level;
+        console_log::init_with_level(Level::Info).expect("failed to init logger");
+
+        crate::main()
+    }
+}
@@ -0,0 +1,114 @@
+//! render resolution lowered while frames are slow.
+//!
+//! canvas keeps its css size, so layout stays the same. only backing store shrinks
+//! and browser scales it up.
+
+/// settings of [`Renderer::set_adaptive_resolution`](crate::Renderer::set_adaptive_resolution).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveResolution {
+    /// paint time in milliseconds frames should stay under.
+    pub frame_budget_ms: f64,
+    /// lowest render scale, as fraction of device pixel ratio.
+    pub min_scale: f64,
+}
+
+impl Default for AdaptiveResolution {
+    fn default() -> Self {
+        Self {
+            frame_budget_ms: 12.0,
+            min_scale: 0.5,
+        }
+    }
+}
+
+/// one step of scale. painting cost goes with pixel count, so step squared.
+const STEP: f64 = 0.8;
+/// frames averaged after each change, before scale moves again.
+const SETTLE_FRAMES: u32 = 8;
+/// raise scale only below this part of budget. one step up costs 1 / STEP^2 = 1.56 times,
+/// so raised frames stay in budget and scale does not swing.
+const HEADROOM: f64 = 0.5;
+
+/// picks render scale from recent paint times.
+pub(crate) struct ResolutionGovernor {
+    scale: f64,
+    /// moving average of paint time since last change.
+    average_ms: Option<f64>,
+    frames: u32,
+}
+
+impl Default for ResolutionGovernor {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            average_ms: None,
+            frames: 0,
+        }
+    }
+}
+
+impl ResolutionGovernor {
+    /// fraction of device pixel ratio to render at.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+    pub fn record(&mut self, paint_ms: f64, settings: &AdaptiveResolution) {
+        let average = match self.average_ms {
+            Some(average) => average * 0.9 + paint_ms * 0.1,
+            None => paint_ms,
+        };
+        self.average_ms = Some(average);
+        self.frames += 1;
+        if self.frames < SETTLE_FRAMES {
+            return;
+        }
+        let scale = if average > settings.frame_budget_ms {
+            (self.scale * STEP).max(settings.min_scale)
+        } else if average < settings.frame_budget_ms * HEADROOM {
+            (self.scale / STEP).min(1.0)
+        } else {
+            self.scale
+        };
+        if scale != self.scale {
+            *self = Self {
+                scale,
+                ..Default::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_follows_frame_time() {
+        let settings = AdaptiveResolution::default();
+        let mut governor = ResolutionGovernor::default();
+        for _ in 0..100 {
+            governor.record(40.0, &settings);
+        }
+        assert_eq!(governor.scale(), settings.min_scale);
+        // within budget but no headroom, stays.
+        for _ in 0..100 {
+            governor.record(10.0, &settings);
+        }
+        assert_eq!(governor.scale(), settings.min_scale);
+        for _ in 0..100 {
+            governor.record(2.0, &settings);
+        }
+        assert_eq!(governor.scale(), 1.0);
+    }
+
+    #[test]
+    fn single_slow_frame_is_ignored() {
+        let settings = AdaptiveResolution::default();
+        let mut governor = ResolutionGovernor::default();
+        for i in 0..100 {
+            let paint_ms = if i == 50 { 30.0 } else { 8.0 };
+            governor.record(paint_ms, &settings);
+        }
+        assert_eq!(governor.scale(), 1.0);
+    }
+}
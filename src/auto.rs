@@ -0,0 +1,359 @@
+//! pick painter at runtime. glow painter when WebGL works, [`Renderer`] otherwise.
+//!
+//! WebGL needs `webgl` feature. without it [`AutoPainter`] always uses Canvas2D.
+//!
+//! a canvas which once had WebGL context never gives Canvas2D context.
+//! so falling back after WebGL was in use replaces canvas element by a clone of it.
+//! check [`AutoPainter::canvas`] after each frame if you hold listeners on the canvas.
+use crate::{Renderer, TextRenderingMode};
+use epaint::{textures::TexturesDelta, ClippedShape, Color32};
+use web_sys::HtmlCanvasElement;
+
+#[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+use webgl::GlowPainter;
+
+enum Backend {
+    #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+    Glow(GlowPainter),
+    Canvas(Renderer),
+}
+
+/// painter with same frame api as [`Renderer`].
+pub struct AutoPainter {
+    backend: Backend,
+    canvas: HtmlCanvasElement,
+    rendering_mode: TextRenderingMode,
+    clear_color: Color32,
+}
+
+impl AutoPainter {
+    /// use WebGL if it works on `canvas`, else Canvas2D.
+    pub fn new(canvas: &HtmlCanvasElement) -> Option<Self> {
+        #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+        if webgl::probe() {
+            match GlowPainter::new(canvas) {
+                Ok(painter) => {
+                    return Some(Self {
+                        backend: Backend::Glow(painter),
+                        canvas: canvas.clone(),
+                        rendering_mode: TextRenderingMode::EGUI,
+                        clear_color: Color32::BLACK,
+                    });
+                }
+                Err(err) => {
+                    log::warn!("glow painter failed, use Canvas2D: {}", err);
+                    let canvas = replace_canvas(canvas)?;
+                    return Self::new_canvas(&canvas);
+                }
+            }
+        }
+        Self::new_canvas(canvas)
+    }
+    /// always use Canvas2D.
+    pub fn new_canvas(canvas: &HtmlCanvasElement) -> Option<Self> {
+        Some(Self {
+            backend: Backend::Canvas(Renderer::new_with_canvas(canvas)?),
+            canvas: canvas.clone(),
+            rendering_mode: TextRenderingMode::EGUI,
+            clear_color: Color32::BLACK,
+        })
+    }
+    /// canvas painted now. changes when WebGL painter falls back.
+    pub fn canvas(&self) -> &HtmlCanvasElement {
+        &self.canvas
+    }
+    pub fn is_webgl(&self) -> bool {
+        !matches!(self.backend, Backend::Canvas(_))
+    }
+    /// text rendering mode of Canvas2D painter. glow painter always blits font atlas.
+    #[allow(irrefutable_let_patterns)]
+    pub fn set_rendering_mode(&mut self, rendering_mode: TextRenderingMode) {
+        self.rendering_mode = rendering_mode;
+        if let Backend::Canvas(renderer) = &mut self.backend {
+            renderer.set_rendering_mode(rendering_mode);
+        }
+    }
+    pub fn clear(&mut self, color: &Color32) {
+        self.clear_color = *color;
+        match &mut self.backend {
+            #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+            Backend::Glow(painter) => painter.clear(&self.canvas, color),
+            Backend::Canvas(renderer) => renderer.clear(color),
+        }
+    }
+    pub fn paint_and_update_texture(
+        &mut self,
+        shapes: &[ClippedShape],
+        textures_delta: TexturesDelta,
+    ) {
+        match &mut self.backend {
+            #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+            Backend::Glow(painter) => {
+                if let Err(err) = painter.paint(&self.canvas, shapes, &textures_delta) {
+                    log::warn!("glow painter failed, use Canvas2D: {}", err);
+                    self.fall_back(shapes);
+                }
+            }
+            Backend::Canvas(renderer) => renderer.paint_and_update_texture(shapes, textures_delta),
+        }
+    }
+    /// same as [`Renderer::render_full_output`].
+    pub fn render_full_output(
+        &mut self,
+        clear_color: &Color32,
+        full_output: egui::FullOutput,
+    ) -> (egui::PlatformOutput, bool) {
+        let egui::FullOutput {
+            platform_output,
+            needs_repaint,
+            textures_delta,
+            shapes,
+        } = full_output;
+        self.clear(clear_color);
+        self.paint_and_update_texture(&shapes, textures_delta);
+        (platform_output, needs_repaint)
+    }
+    /// switch to Canvas2D and repaint `shapes` of failed frame there.
+    #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+    fn fall_back(&mut self, shapes: &[ClippedShape]) {
+        let canvas = match replace_canvas(&self.canvas) {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let mut renderer = match Renderer::new_with_canvas(&canvas) {
+            Some(renderer) => renderer,
+            None => return,
+        };
+        renderer.set_rendering_mode(self.rendering_mode);
+        let textures_delta = match &self.backend {
+            Backend::Glow(painter) => painter.textures_delta(),
+            Backend::Canvas(_) => return,
+        };
+        renderer.clear(&self.clear_color);
+        renderer.paint_and_update_texture(shapes, textures_delta);
+        self.canvas = canvas;
+        // drops glow painter, which releases GL resources.
+        self.backend = Backend::Canvas(renderer);
+    }
+}
+
+/// put fresh clone of `canvas` at its place in document.
+#[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+fn replace_canvas(canvas: &HtmlCanvasElement) -> Option<HtmlCanvasElement> {
+    use wasm_bindgen::JsCast;
+    let fresh: HtmlCanvasElement = canvas.clone_node().ok()?.dyn_into().ok()?;
+    if canvas.parent_node().is_some() {
+        canvas.replace_with_with_node_1(&fresh).ok()?;
+    }
+    Some(fresh)
+}
+
+#[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+mod webgl {
+    use crate::cpu_texture::CpuTextures;
+    use epaint::{
+        tessellator::{tessellate_shapes, TessellationOptions},
+        textures::TexturesDelta,
+        ClippedShape, Color32, TextureId,
+    };
+    use glow::HasContext;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+    use web_sys::{
+        HtmlCanvasElement, WebGl2RenderingContext, WebGlRenderingContext, WebglLoseContext,
+    };
+
+    /// frames in a row with GL error before giving up.
+    const MAX_ERROR_FRAMES: u32 = 3;
+
+    /// try WebGL on a scratch canvas, so the real one stays usable for Canvas2D.
+    ///
+    /// its context is lost right away. browsers keep about 16 alive, until gc.
+    pub fn probe() -> bool {
+        let canvas = match web_sys::window()
+            .and_then(|win| win.document())
+            .and_then(|doc| doc.create_element("canvas").ok())
+            .and_then(|elem| elem.dyn_into::<HtmlCanvasElement>().ok())
+        {
+            Some(canvas) => canvas,
+            None => return false,
+        };
+        let gl2 = canvas
+            .get_context("webgl2")
+            .ok()
+            .flatten()
+            .and_then(|ctx| ctx.dyn_into::<WebGl2RenderingContext>().ok());
+        if let Some(gl2) = gl2 {
+            let works = !gl2.is_context_lost();
+            lose_context(gl2.get_extension("WEBGL_lose_context"));
+            return works;
+        }
+        let gl1 = canvas
+            .get_context("webgl")
+            .ok()
+            .flatten()
+            .and_then(|ctx| ctx.dyn_into::<WebGlRenderingContext>().ok());
+        if let Some(gl1) = gl1 {
+            let works = !gl1.is_context_lost();
+            lose_context(gl1.get_extension("WEBGL_lose_context"));
+            return works;
+        }
+        false
+    }
+
+    fn lose_context(extension: Result<Option<js_sys::Object>, wasm_bindgen::JsValue>) {
+        // extension interfaces aren't globals, so no `instanceof` check.
+        if let Some(extension) = extension.ok().flatten() {
+            extension
+                .unchecked_into::<WebglLoseContext>()
+                .lose_context();
+        }
+    }
+
+    fn gl_context(canvas: &HtmlCanvasElement) -> Option<glow::Context> {
+        let gl2 = canvas
+            .get_context("webgl2")
+            .ok()
+            .flatten()
+            .and_then(|ctx| ctx.dyn_into::<WebGl2RenderingContext>().ok());
+        if let Some(gl2) = gl2 {
+            if !gl2.is_context_lost() {
+                return Some(glow::Context::from_webgl2_context(gl2));
+            }
+        }
+        let gl1 = canvas
+            .get_context("webgl")
+            .ok()
+            .flatten()
+            .and_then(|ctx| ctx.dyn_into::<WebGlRenderingContext>().ok())?;
+        if gl1.is_context_lost() {
+            return None;
+        }
+        Some(glow::Context::from_webgl1_context(gl1))
+    }
+
+    /// size backing store to css size times device pixel ratio, as [`crate::Renderer`] does.
+    /// returns pixels per point.
+    fn fit_canvas(canvas: &HtmlCanvasElement) -> f32 {
+        let dpr = web_sys::window()
+            .map(|win| win.device_pixel_ratio())
+            .unwrap_or(1.0);
+        let rect = canvas.get_bounding_client_rect();
+        let (width, height) = (
+            (rect.width() * dpr).round() as u32,
+            (rect.height() * dpr).round() as u32,
+        );
+        // not laid out yet. setting same size would still clear canvas.
+        let resized = canvas.width() != width || canvas.height() != height;
+        if width > 0 && height > 0 && resized {
+            canvas.set_width(width);
+            canvas.set_height(height);
+        }
+        dpr as f32
+    }
+
+    pub struct GlowPainter {
+        gl: Rc<glow::Context>,
+        painter: egui_glow::Painter,
+        /// copies to hand over to Canvas2D on fall back. also gives font atlas size.
+        textures: CpuTextures,
+        error_frames: u32,
+        context_lost: Rc<Cell<bool>>,
+    }
+
+    impl GlowPainter {
+        pub fn new(canvas: &HtmlCanvasElement) -> Result<Self, String> {
+            let gl = Rc::new(gl_context(canvas).ok_or("no WebGL context")?);
+            let painter = egui_glow::Painter::new(gl.clone(), None, "")?;
+            let context_lost = Rc::new(Cell::new(false));
+            {
+                let context_lost = context_lost.clone();
+                let listener = Closure::wrap(Box::new(move || {
+                    context_lost.set(true);
+                }) as Box<dyn FnMut()>);
+                canvas
+                    .add_event_listener_with_callback(
+                        "webglcontextlost",
+                        listener.as_ref().unchecked_ref(),
+                    )
+                    .map_err(|err| format!("{:?}", err))?;
+                listener.forget();
+            }
+            fit_canvas(canvas);
+            Ok(Self {
+                gl,
+                painter,
+                textures: CpuTextures::default(),
+                error_frames: 0,
+                context_lost,
+            })
+        }
+        /// also follows canvas size. resizing clears canvas, so do it before painting.
+        pub fn clear(&self, canvas: &HtmlCanvasElement, color: &Color32) {
+            fit_canvas(canvas);
+            unsafe {
+                self.gl.disable(glow::SCISSOR_TEST);
+                self.gl
+                    .viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
+                self.gl.clear_color(
+                    color.r() as f32 / 255.0,
+                    color.g() as f32 / 255.0,
+                    color.b() as f32 / 255.0,
+                    color.a() as f32 / 255.0,
+                );
+                self.gl.clear(glow::COLOR_BUFFER_BIT);
+            }
+        }
+        pub fn paint(
+            &mut self,
+            canvas: &HtmlCanvasElement,
+            shapes: &[ClippedShape],
+            textures_delta: &TexturesDelta,
+        ) -> Result<(), String> {
+            self.textures.update(textures_delta);
+            if self.context_lost.get() {
+                return Err("WebGL context lost".into());
+            }
+            // frames painted without clear still follow resizes.
+            let pixels_per_point = fit_canvas(canvas);
+            let tex_size = self
+                .textures
+                .get(&TextureId::default())
+                .map(|image| image.size)
+                .unwrap_or([1, 1]);
+            let options = TessellationOptions {
+                pixels_per_point,
+                aa_size: 1.0 / pixels_per_point,
+                ..Default::default()
+            };
+            let meshes = tessellate_shapes(shapes.to_vec(), options, tex_size);
+            self.painter.paint_and_update_textures(
+                [canvas.width(), canvas.height()],
+                pixels_per_point,
+                meshes,
+                textures_delta,
+            );
+            let error = unsafe { self.gl.get_error() };
+            if error == glow::NO_ERROR {
+                self.error_frames = 0;
+            } else {
+                self.error_frames += 1;
+                if self.error_frames >= MAX_ERROR_FRAMES {
+                    return Err(format!("GL error 0x{:x}", error));
+                }
+            }
+            Ok(())
+        }
+        pub fn textures_delta(&self) -> TexturesDelta {
+            self.textures.full_delta()
+        }
+    }
+
+    impl Drop for GlowPainter {
+        fn drop(&mut self) {
+            self.painter.destroy();
+        }
+    }
+}
@@ -0,0 +1,1657 @@
+//! shape to Canvas2D translation.
+//!
+//! [`Renderer`](crate::Renderer) paints into browser canvas.
+//! other targets (svg export etc) implement [`Canvas2d`] and reuse same translation.
+use crate::command::CommandBuffer;
+use crate::text_cache::TextCache;
+use crate::texture::{
+    self, TextureFilter, TextureOptions, TextureSource, TiledTexture, WebTexture,
+};
+use crate::{ShadowRenderingMode, TextRenderingMode};
+use epaint::{
+    tessellator::{TessellationOptions, Tessellator},
+    text::Glyph,
+    CircleShape, ClippedMesh, ClippedShape, Color32, CubicBezierShape, Mesh, PathShape, Pos2,
+    QuadraticBezierShape, Rect, RectShape, Shape, Stroke, TextShape, TextureId, Vec2, Vertex,
+};
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::sync::atomic::{AtomicBool, Ordering};
+use wasm_bindgen::__rt::IntoJsResult;
+use web_sys::CanvasRenderingContext2d;
+
+/// subset of Canvas2D api used by shape painting.
+///
+/// methods follow `CanvasRenderingContext2D` of same name.
+pub trait Canvas2d {
+    fn begin_path(&mut self);
+    fn close_path(&mut self);
+    fn move_to(&mut self, x: f64, y: f64);
+    fn line_to(&mut self, x: f64, y: f64);
+    /// clockwise arc.
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64);
+    fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64);
+    fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64);
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64);
+    fn set_line_width(&mut self, width: f64);
+    fn set_fill_color(&mut self, color: Color32);
+    fn set_stroke_color(&mut self, color: Color32);
+    fn fill(&mut self);
+    fn stroke(&mut self);
+    fn save(&mut self);
+    fn restore(&mut self);
+    fn clip(&mut self);
+    /// `drawImage` with source rectangle in texels of texture `id`.
+    fn draw_texture(&mut self, id: TextureId, source: Rect, dest: Rect);
+    /// [`Self::draw_texture`] with texels multiplied by `tint`, as vertex color of egui meshes.
+    ///
+    /// targets without tinting draw texture as is.
+    fn draw_texture_tinted(&mut self, id: TextureId, source: Rect, dest: Rect, _tint: Color32) {
+        self.draw_texture(id, source, dest);
+    }
+    /// [`Self::draw_texture_tinted`] with texels mapped to points by affine `transform`,
+    /// `[a, b, c, d, e, f]` as canvas `transform()` takes it. for rotated, flipped or sheared uv.
+    ///
+    /// false when target can't transform images.
+    fn draw_texture_transformed(
+        &mut self,
+        _id: TextureId,
+        _source: Rect,
+        _transform: [f32; 6],
+        _tint: Color32,
+    ) -> bool {
+        false
+    }
+    /// css font.
+    fn set_font(&mut self, font: &str);
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: f64);
+    /// run paint callback `id` on area `rect`. false when target has no such callback.
+    fn paint_callback(&mut self, _id: u64, _rect: Rect) -> bool {
+        false
+    }
+    /// draw only the blurred shadow of polygon `points`. `blur` is the width of the fade in points.
+    /// false when target leaves shadows to mesh painting.
+    fn fill_shadow(&mut self, _points: &[Pos2], _blur: f64, _color: Color32) -> bool {
+        false
+    }
+    /// size of texture `id` in texels. `None` before it is uploaded.
+    fn texture_size(&self, id: TextureId) -> Option<[usize; 2]>;
+    /// draw `text` from bitmap painted earlier. false when target paints text each time.
+    fn draw_cached_text(&mut self, _text: &TextShape, _rendering_mode: TextRenderingMode) -> bool {
+        false
+    }
+    /// distance in points paths may be simplified by. `None` keeps every point.
+    fn path_tolerance(&self) -> Option<f32> {
+        None
+    }
+    /// true when nothing inside `bounds` can be seen, so painting it is skipped.
+    /// targets which cull count what they skip.
+    fn culls(&mut self, _bounds: Rect) -> bool {
+        false
+    }
+    /// fill current path with `createLinearGradient(x0, y0, x1, y1)`.
+    ///
+    /// targets without gradients fill with middle color.
+    fn fill_linear_gradient(
+        &mut self,
+        _x0: f64,
+        _y0: f64,
+        _x1: f64,
+        _y1: f64,
+        colors: [Color32; 2],
+    ) {
+        let [a, b] = [colors[0].to_array(), colors[1].to_array()];
+        let mid = |i: usize| ((a[i] as u16 + b[i] as u16) / 2) as u8;
+        self.set_fill_color(Color32::from_rgba_premultiplied(
+            mid(0),
+            mid(1),
+            mid(2),
+            mid(3),
+        ));
+        self.fill();
+    }
+}
+
+/// custom Canvas2D drawing inside egui shapes. called with context and target rect in points.
+///
+/// clip rect of the shape is applied, also when the marker lies inside it,
+/// and context state is restored after the call.
+pub type PaintCallback = Box<dyn FnMut(&CanvasRenderingContext2d, Rect)>;
+
+/// marker shape calling paint callback `id` with `rect` when painted.
+///
+/// register the callback by [`Renderer::set_paint_callback`](crate::Renderer::set_paint_callback).
+/// other targets draw nothing for it.
+pub fn callback_shape(id: u64, rect: Rect) -> Shape {
+    let mut mesh = Mesh::with_texture(TextureId::User(id));
+    mesh.add_rect_with_uv(
+        rect,
+        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+        Color32::WHITE,
+    );
+    Shape::mesh(mesh)
+}
+
+/// css color used by canvas.
+pub(crate) fn color_text(color: &Color32) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.r(),
+        color.g(),
+        color.b(),
+        color.a()
+    )
+}
+
+/// gradient stops for `colors`. fully transparent end takes color of the other end,
+/// so a fade out doesn't get dark halfway when interpolated without premultiplying.
+pub(crate) fn gradient_stops(colors: [Color32; 2]) -> [Color32; 2] {
+    let fade = |from: Color32, to: Color32| {
+        if to == Color32::TRANSPARENT {
+            Color32::from_rgba_premultiplied(from.r(), from.g(), from.b(), 0)
+        } else {
+            to
+        }
+    };
+    [fade(colors[1], colors[0]), fade(colors[0], colors[1])]
+}
+
+/// canvas `arc` as cubic beziers, for targets without arcs. one bezier per quarter turn.
+///
+/// returns start point and `[control1, control2, end]` of each bezier.
+#[allow(clippy::type_complexity)]
+pub(crate) fn arc_to_cubics(
+    x: f64,
+    y: f64,
+    radius: f64,
+    start_angle: f64,
+    end_angle: f64,
+) -> ((f64, f64), Vec<[(f64, f64); 3]>) {
+    let at = |angle: f64| (x + radius * angle.cos(), y + radius * angle.sin());
+    let start = at(start_angle);
+    if radius <= 0.0 {
+        return (start, Vec::new());
+    }
+    // clockwise. canvas wraps negative sweep.
+    let sweep = (end_angle - start_angle).min(2.0 * PI);
+    let sweep = if sweep < 0.0 {
+        sweep.rem_euclid(2.0 * PI)
+    } else {
+        sweep
+    };
+    let segments = (sweep / FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = sweep / segments as f64;
+    let k = 4.0 / 3.0 * (step / 4.0).tan() * radius;
+    let curves = (0..segments)
+        .map(|i| {
+            let a0 = start_angle + step * i as f64;
+            let a1 = a0 + step;
+            let p0 = at(a0);
+            let p3 = at(a1);
+            let c1 = (p0.0 - k * a0.sin(), p0.1 + k * a0.cos());
+            let c2 = (p3.0 + k * a1.sin(), p3.1 - k * a1.cos());
+            [c1, c2, p3]
+        })
+        .collect();
+    (start, curves)
+}
+
+/// browser canvas and textures.
+pub(crate) struct WebCanvas<'a> {
+    pub context: &'a CanvasRenderingContext2d,
+    pub textures: &'a HashMap<TextureId, WebTexture>,
+    pub tiled: &'a HashMap<TextureId, TiledTexture>,
+    pub texture_options: &'a HashMap<TextureId, TextureOptions>,
+    pub callbacks: &'a mut HashMap<u64, PaintCallback>,
+    pub shadows: ShadowRenderingMode,
+    pub dpr: f64,
+    /// clip rect of current shape within canvas.
+    pub visible: Rect,
+    pub culled: usize,
+    pub path_tolerance: Option<f32>,
+    /// `None` paints text each time.
+    pub text_cache: Option<&'a mut TextCache>,
+    /// `None` calls context right away.
+    pub commands: Option<&'a mut CommandBuffer>,
+    /// canvas calls made right away, not buffered. text painted into cache is not counted.
+    pub direct_calls: usize,
+}
+
+/// color of tinted copy for premultiplied `tint`, whose alpha is not zero.
+fn tint_rgb(tint: Color32) -> [u8; 3] {
+    let [r, g, b, a] = tint.to_array();
+    let unmultiply = |c: u8| ((c as u32 * 255 + a as u32 / 2) / a as u32).min(255) as u8;
+    [unmultiply(r), unmultiply(g), unmultiply(b)]
+}
+
+/// canvas call, buffered when there is command buffer.
+macro_rules! call {
+    ($canvas:expr, $method:ident($($arg:expr),*)) => {
+        match &mut $canvas.commands {
+            Some(commands) => commands.$method($($arg),*),
+            None => {
+                $canvas.direct_calls += 1;
+                $canvas.context.$method($($arg),*)
+            }
+        }
+    };
+}
+
+impl WebCanvas<'_> {
+    /// run buffered calls. needed before calling context directly
+    /// and before canvases buffered calls draw from are released.
+    pub fn flush(&mut self) {
+        if let Some(commands) = &mut self.commands {
+            commands.flush(self.context);
+        }
+    }
+    /// draw `source` texels of one canvas of texture `id`.
+    fn draw_tile(
+        &mut self,
+        texture: &WebTexture,
+        id: TextureId,
+        source: Rect,
+        dest: Rect,
+        tint: Color32,
+    ) {
+        if tint.a() == 0 {
+            return;
+        }
+        let options = self
+            .texture_options
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| TextureOptions::default_for(id));
+        match &mut self.commands {
+            Some(commands) => commands.set_filter(options.filter),
+            None => {
+                texture::apply_filter(self.context, options.filter);
+                // smoothing quality is a second call.
+                self.direct_calls +=
+                    1 + matches!(options.filter, TextureFilter::Linear(_)) as usize;
+            }
+        }
+        let a = tint.a();
+        let rgb = tint_rgb(tint);
+        let (image, source) = if rgb == [255; 3] {
+            // device pixels per texel
+            let scale = self.dpr as f32
+                * (dest.width() / source.width()).min(dest.height() / source.height());
+            texture.select(source, scale)
+        } else {
+            if !texture.tints.contains(rgb) {
+                // new copy may release one drawn by buffered calls.
+                self.flush();
+            }
+            (TextureSource::Canvas(texture.tinted(rgb)), source)
+        };
+        // alpha of tint needs no copy.
+        call!(self, set_global_alpha(a as f64 / 255.0));
+        let (sx, sy, sw, sh) = (
+            source.min.x as f64,
+            source.min.y as f64,
+            source.width() as f64,
+            source.height() as f64,
+        );
+        let (dx, dy, dw, dh) = (
+            dest.min.x as f64,
+            dest.min.y as f64,
+            dest.width() as f64,
+            dest.height() as f64,
+        );
+        if let Some(commands) = &mut self.commands {
+            let image = match image {
+                TextureSource::Canvas(canvas) => canvas.into(),
+                TextureSource::Bitmap(bitmap) => bitmap.into(),
+            };
+            commands.draw_image(image, [sx, sy, sw, sh], [dx, dy, dw, dh]);
+            commands.set_global_alpha(1.0);
+            return;
+        }
+        match image {
+            TextureSource::Canvas(canvas) => self
+                .context
+                .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                    &canvas, sx, sy, sw, sh, dx, dy, dw, dh,
+                ),
+            TextureSource::Bitmap(bitmap) => self
+                .context
+                .draw_image_with_image_bitmap_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                    &bitmap, sx, sy, sw, sh, dx, dy, dw, dh,
+                ),
+        }
+        .unwrap();
+        self.context.set_global_alpha(1.0);
+        self.direct_calls += 2;
+    }
+}
+
+impl Canvas2d for WebCanvas<'_> {
+    fn begin_path(&mut self) {
+        call!(self, begin_path());
+    }
+    fn close_path(&mut self) {
+        call!(self, close_path());
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        call!(self, move_to(x, y));
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        call!(self, line_to(x, y));
+    }
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        match &mut self.commands {
+            Some(commands) => commands.arc(x, y, radius, start_angle, end_angle),
+            None => {
+                self.direct_calls += 1;
+                self.context
+                    .arc(x, y, radius, start_angle, end_angle)
+                    .unwrap()
+            }
+        }
+    }
+    fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        call!(self, quadratic_curve_to(cpx, cpy, x, y));
+    }
+    fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        call!(self, bezier_curve_to(cp1x, cp1y, cp2x, cp2y, x, y));
+    }
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        call!(self, rect(x, y, w, h));
+    }
+    fn set_line_width(&mut self, width: f64) {
+        call!(self, set_line_width(width));
+    }
+    fn set_fill_color(&mut self, color: Color32) {
+        let color = color_text(&color);
+        match &mut self.commands {
+            Some(commands) => commands.set_fill_style(&color),
+            None => {
+                self.direct_calls += 1;
+                self.context
+                    .set_fill_style(&color.into_js_result().unwrap())
+            }
+        }
+    }
+    fn set_stroke_color(&mut self, color: Color32) {
+        let color = color_text(&color);
+        match &mut self.commands {
+            Some(commands) => commands.set_stroke_style(&color),
+            None => {
+                self.direct_calls += 1;
+                self.context
+                    .set_stroke_style(&color.into_js_result().unwrap())
+            }
+        }
+    }
+    fn fill(&mut self) {
+        call!(self, fill());
+    }
+    fn stroke(&mut self) {
+        call!(self, stroke());
+    }
+    fn save(&mut self) {
+        call!(self, save());
+    }
+    fn restore(&mut self) {
+        call!(self, restore());
+    }
+    fn clip(&mut self) {
+        call!(self, clip());
+    }
+    fn draw_texture(&mut self, id: TextureId, source: Rect, dest: Rect) {
+        self.draw_texture_tinted(id, source, dest, Color32::WHITE);
+    }
+    fn draw_texture_tinted(&mut self, id: TextureId, source: Rect, dest: Rect, tint: Color32) {
+        let tiled = self.tiled;
+        if let Some(tiled) = tiled.get(&id) {
+            for (index, source, dest) in tiled.split(source, dest) {
+                self.draw_tile(&tiled.tiles[index].1, id, source, dest, tint);
+            }
+            return;
+        }
+        if let Some(texture) = self.textures.get(&id) {
+            self.draw_tile(texture, id, source, dest, tint);
+        }
+    }
+    fn draw_texture_transformed(
+        &mut self,
+        id: TextureId,
+        source: Rect,
+        transform: [f32; 6],
+        tint: Color32,
+    ) -> bool {
+        let [a, b, c, d, e, f] = transform.map(|x| x as f64);
+        self.save();
+        match &mut self.commands {
+            Some(commands) => commands.transform(a, b, c, d, e, f),
+            None => {
+                self.direct_calls += 1;
+                self.context.transform(a, b, c, d, e, f).unwrap()
+            }
+        }
+        // texels are points now.
+        self.draw_texture_tinted(id, source, source, tint);
+        self.restore();
+        true
+    }
+    fn set_font(&mut self, font: &str) {
+        call!(self, set_font(font));
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: f64) {
+        match &mut self.commands {
+            Some(commands) => commands.fill_text(text, x, y, max_width),
+            None => {
+                self.direct_calls += 1;
+                self.context
+                    .fill_text_with_max_width(text, x, y, max_width)
+                    .unwrap()
+            }
+        }
+    }
+    fn paint_callback(&mut self, id: u64, rect: Rect) -> bool {
+        if !self.callbacks.contains_key(&id) {
+            return false;
+        }
+        // marker inside its clip rect gets no clip from `ClipState`, but callback may draw
+        // anywhere.
+        let visible = self.visible;
+        if visible.is_finite() {
+            begin_clip(self, visible);
+        } else {
+            self.save();
+        }
+        // callback draws on context right away.
+        self.flush();
+        if let Some(callback) = self.callbacks.get_mut(&id) {
+            self.context.begin_path();
+            self.direct_calls += 1;
+            callback(self.context, rect);
+        }
+        self.restore();
+        true
+    }
+    fn fill_shadow(&mut self, points: &[Pos2], blur: f64, color: Color32) -> bool {
+        match self.shadows {
+            ShadowRenderingMode::Mesh => return false,
+            ShadowRenderingMode::Skip => return true,
+            ShadowRenderingMode::Blur => {}
+        }
+        // polygon goes left out of sight, and its shadow is offset back into place.
+        // shadow offset and blur are in device pixels, not affected by transform.
+        let max_x = points.iter().map(|p| p.x).fold(0.0, f32::max) as f64;
+        let offset = max_x + 2.0 * blur + 1.0;
+        self.save();
+        match &mut self.commands {
+            Some(commands) => commands.translate(-offset, 0.0),
+            None => {
+                self.direct_calls += 1;
+                self.context.translate(-offset, 0.0).unwrap()
+            }
+        }
+        self.begin_path();
+        for (i, p) in points.iter().enumerate() {
+            if i == 0 {
+                self.move_to(p.x as f64, p.y as f64);
+            } else {
+                self.line_to(p.x as f64, p.y as f64);
+            }
+        }
+        self.close_path();
+        // canvas blur is gaussian with sigma of half shadowBlur. its slope at the edge
+        // matches the linear fade of egui when sigma is 0.4 of fade width.
+        let (offset_x, shadow_blur) = (offset * self.dpr, 0.8 * blur * self.dpr);
+        match &mut self.commands {
+            Some(commands) => commands.set_shadow(offset_x, 0.0, shadow_blur, &color_text(&color)),
+            None => {
+                self.context.set_shadow_offset_x(offset_x);
+                self.context.set_shadow_offset_y(0.0);
+                self.context.set_shadow_blur(shadow_blur);
+                self.context.set_shadow_color(&color_text(&color));
+                self.direct_calls += 4;
+            }
+        }
+        self.set_fill_color(Color32::BLACK);
+        self.fill();
+        self.restore();
+        true
+    }
+    fn texture_size(&self, id: TextureId) -> Option<[usize; 2]> {
+        let [w, h] = match self.tiled.get(&id) {
+            Some(tiled) => tiled.size,
+            None => self.textures.get(&id)?.size(),
+        };
+        Some([w as usize, h as usize])
+    }
+    fn draw_cached_text(&mut self, text: &TextShape, rendering_mode: TextRenderingMode) -> bool {
+        if rendering_mode == TextRenderingMode::EGUI {
+            // glyphs still uploading would be cached blank.
+            match self.textures.get(&TextureId::default()) {
+                Some(atlas) if atlas.uploads.pending() == 0 => {}
+                _ => return false,
+            }
+        }
+        let text_cache = match &mut self.text_cache {
+            Some(text_cache) => text_cache,
+            None => return false,
+        };
+        let dpr = self.dpr;
+        let area = text.galley.rect.expand(text.underline.width + 1.0);
+        let size = [
+            (area.width() as f64 * dpr).ceil() as u32,
+            (area.height() as f64 * dpr).ceil() as u32,
+        ];
+        let key = TextCache::key(text, rendering_mode, dpr);
+        if !text_cache.contains(key) {
+            // miss may evict bitmap drawn by buffered calls.
+            if let Some(commands) = &mut self.commands {
+                commands.flush(self.context);
+            }
+        }
+        let (textures, tiled, texture_options) = (self.textures, self.tiled, self.texture_options);
+        let callbacks = &mut *self.callbacks;
+        let shadows = self.shadows;
+        let bitmap = text_cache.get(key, size, |context| {
+            context.scale(dpr, dpr).unwrap();
+            context
+                .translate(-area.min.x as f64, -area.min.y as f64)
+                .unwrap();
+            let mut offscreen = WebCanvas {
+                context,
+                textures,
+                tiled,
+                texture_options,
+                callbacks,
+                shadows,
+                dpr,
+                visible: Rect::EVERYTHING,
+                culled: 0,
+                path_tolerance: None,
+                text_cache: None,
+                commands: None,
+                direct_calls: 0,
+            };
+            let text = TextShape {
+                pos: Pos2::ZERO,
+                ..text.clone()
+            };
+            paint_text(&mut offscreen, rendering_mode, &text);
+        });
+        let bitmap = match bitmap {
+            Some(bitmap) => bitmap,
+            None => return false,
+        };
+        // bitmap texels on device pixels. text moves by half a pixel at most.
+        let snap = |x: f32| (x as f64 * dpr).round() / dpr;
+        let dest = [
+            snap(text.pos.x + area.min.x),
+            snap(text.pos.y + area.min.y),
+            size[0] as f64 / dpr,
+            size[1] as f64 / dpr,
+        ];
+        match &mut self.commands {
+            Some(commands) => commands.draw_image_scaled(bitmap.into(), dest),
+            None => {
+                let [dx, dy, dw, dh] = dest;
+                self.context
+                    .draw_image_with_html_canvas_element_and_dw_and_dh(&bitmap, dx, dy, dw, dh)
+                    .unwrap();
+                self.direct_calls += 1;
+            }
+        }
+        true
+    }
+    fn path_tolerance(&self) -> Option<f32> {
+        self.path_tolerance
+    }
+    fn culls(&mut self, bounds: Rect) -> bool {
+        // empty shapes, not worth counting.
+        if !bounds.is_positive() {
+            return true;
+        }
+        let culled = !bounds.intersects(self.visible);
+        self.culled += culled as usize;
+        culled
+    }
+    fn fill_linear_gradient(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, colors: [Color32; 2]) {
+        if let Some(commands) = &mut self.commands {
+            let [from, to] = gradient_stops(colors).map(|color| color_text(&color));
+            commands.fill_linear_gradient([x0, y0, x1, y1], [&from, &to]);
+            return;
+        }
+        let gradient = self.context.create_linear_gradient(x0, y0, x1, y1);
+        let [from, to] = gradient_stops(colors);
+        for (offset, color) in [(0.0, from), (1.0, to)] {
+            gradient
+                .add_color_stop(offset, &color_text(&color))
+                .unwrap();
+        }
+        self.context.set_fill_style(&gradient);
+        self.context.fill();
+        // create, two stops, style and fill.
+        self.direct_calls += 5;
+    }
+}
+
+/// paint shape inside its clip rectangle.
+pub fn paint_clipped<C: Canvas2d>(
+    canvas: &mut C,
+    rendering_mode: TextRenderingMode,
+    shape: &ClippedShape,
+) {
+    begin_clip(canvas, shape.0);
+    paint_shape(canvas, rendering_mode, &shape.1);
+    canvas.restore();
+}
+
+/// paint tessellated mesh inside its clip rectangle.
+pub fn paint_clipped_mesh<C: Canvas2d>(canvas: &mut C, mesh: &ClippedMesh) {
+    begin_clip(canvas, mesh.0);
+    paint_mesh(canvas, &mesh.1);
+    canvas.restore();
+}
+
+/// paint shape through epaint tessellator inside its clip rectangle.
+pub fn paint_clipped_tessellated<C: Canvas2d>(
+    canvas: &mut C,
+    rendering_mode: TextRenderingMode,
+    options: TessellationOptions,
+    shape: &ClippedShape,
+) {
+    begin_clip(canvas, shape.0);
+    paint_tessellated(canvas, rendering_mode, options, &shape.1);
+    canvas.restore();
+}
+
+/// paint shape through epaint tessellator, as the triangles GPU painters get.
+///
+/// [`TextRenderingMode::Browser`] text has no glyphs in font atlas, so it is painted as canvas text.
+pub fn paint_tessellated<C: Canvas2d>(
+    canvas: &mut C,
+    rendering_mode: TextRenderingMode,
+    options: TessellationOptions,
+    shape: &Shape,
+) {
+    match shape {
+        // one mesh has one texture. children may differ.
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                if !canvas.culls(paint_bounds(shape)) {
+                    paint_tessellated(canvas, rendering_mode, options, shape);
+                }
+            }
+        }
+        Shape::Text(_) if rendering_mode == TextRenderingMode::Browser => {
+            paint_shape(canvas, rendering_mode, shape);
+        }
+        _ => {
+            let tex_size = canvas.texture_size(TextureId::default()).unwrap_or([1, 1]);
+            let mut mesh = Mesh::default();
+            Tessellator::from_options(options).tessellate_shape(tex_size, shape.clone(), &mut mesh);
+            paint_mesh(canvas, &mesh);
+        }
+    }
+}
+
+/// clip of consecutive shapes. canvas state changes only when clip rect changes,
+/// and shapes inside their clip rect are painted without clipping.
+/// paint callbacks, which may draw outside their marker, clip themselves.
+#[derive(Default)]
+pub struct ClipState {
+    /// clip rect saved and applied on canvas.
+    active: Option<Rect>,
+}
+
+impl ClipState {
+    /// prepare canvas for painting `bounds` inside `clip_rect`.
+    pub fn apply<C: Canvas2d>(&mut self, canvas: &mut C, clip_rect: Rect, bounds: Rect) {
+        let inside = clip_rect.contains_rect(bounds);
+        if let Some(active) = self.active {
+            if active == clip_rect || (inside && active.contains_rect(bounds)) {
+                return;
+            }
+        }
+        self.reset(canvas);
+        if !inside {
+            begin_clip(canvas, clip_rect);
+            self.active = Some(clip_rect);
+        }
+    }
+    /// drop clip. call after the last shape.
+    pub fn reset<C: Canvas2d>(&mut self, canvas: &mut C) {
+        if self.active.take().is_some() {
+            canvas.restore();
+        }
+    }
+}
+
+/// area painting `shape` may touch, anti aliasing and miter joins included.
+pub fn paint_bounds(shape: &Shape) -> Rect {
+    // canvas default miterLimit.
+    const MITER_LIMIT: f32 = 10.0;
+    let joined = |points: &[Pos2], stroke: &Stroke| {
+        Rect::from_points(points).expand(stroke.width * MITER_LIMIT / 2.0)
+    };
+    let rect = match shape {
+        Shape::Noop => return Rect::NOTHING,
+        Shape::Vec(shapes) => {
+            return shapes
+                .iter()
+                .fold(Rect::NOTHING, |rect, shape| rect.union(paint_bounds(shape)))
+        }
+        Shape::Circle(circle) => {
+            Rect::from_center_size(circle.center, Vec2::splat(2.0 * circle.radius))
+                .expand(circle.stroke.width / 2.0)
+        }
+        Shape::LineSegment { points, stroke } => {
+            Rect::from_two_pos(points[0], points[1]).expand(stroke.width / 2.0)
+        }
+        Shape::Path(path) => joined(&path.points, &path.stroke),
+        // right angle corners reach half width times sqrt 2.
+        Shape::Rect(rect) => rect.rect.expand(rect.stroke.width),
+        Shape::Text(text) => text.visual_bounding_rect().expand(text.underline.width),
+        Shape::Mesh(mesh) => mesh.calc_bounds(),
+        // curve stays inside its control points.
+        Shape::QuadraticBezier(bezier) => joined(&bezier.points, &bezier.stroke),
+        Shape::CubicBezier(bezier) => joined(&bezier.points, &bezier.stroke),
+    };
+    rect.expand(1.0)
+}
+
+/// save state and clip to `rect`. pair with `restore`.
+fn begin_clip<C: Canvas2d>(canvas: &mut C, rect: Rect) {
+    canvas.begin_path();
+    canvas.save();
+    canvas.rect(
+        rect.min.x as f64,
+        rect.min.y as f64,
+        rect.width() as f64,
+        rect.height() as f64,
+    );
+    canvas.clip();
+}
+
+/// premultiplied average of `colors`.
+fn mean_color(colors: &[Color32]) -> Color32 {
+    let mean = |n: usize| {
+        let sum: u32 = colors.iter().map(|c| c.to_array()[n] as u32).sum();
+        ((sum + colors.len() as u32 / 2) / colors.len() as u32) as u8
+    };
+    Color32::from_rgba_premultiplied(mean(0), mean(1), mean(2), mean(3))
+}
+
+/// set once a target couldn't draw transformed texture, so it is logged once.
+static TRANSFORM_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// how a run of mesh triangles is filled. a run is one canvas call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TriangleFill {
+    Color(Color32),
+    /// vertex colors varying along one direction. `colors[i]` at `points[i]`.
+    Gradient([Pos2; 2], [Color32; 2]),
+    /// texels mapped to points by `transform`, as in [`Canvas2d::draw_texture_transformed`].
+    /// `source` covers the run in texels.
+    Texture {
+        transform: [f32; 6],
+        source: Rect,
+        tint: Color32,
+    },
+}
+
+impl TriangleFill {
+    /// fill of one triangle. `None` when there is nothing to draw.
+    fn of(vertices: [&Vertex; 3], tex_size: Option<[usize; 2]>) -> Option<Self> {
+        let [a, b, c] = vertices.map(|v| v.pos);
+        if (b - a).x * (c - a).y - (b - a).y * (c - a).x == 0.0 {
+            return None;
+        }
+        let textured = !(vertices[0].uv == vertices[1].uv && vertices[1].uv == vertices[2].uv);
+        match tex_size {
+            // all vertices on one texel, which is white for shapes in font atlas.
+            _ if !textured => Some(Self::vertex_colors(vertices)),
+            Some(size) => Self::texture_mapping(vertices, size),
+            None => None,
+        }
+    }
+    fn vertex_colors(vertices: [&Vertex; 3]) -> Self {
+        let colors = vertices.map(|v| v.color);
+        if colors[0] == colors[1] && colors[1] == colors[2] {
+            return Self::Color(colors[0]);
+        }
+        let rgba = colors.map(|c| c.to_array().map(|c| c as f32));
+        let diff = |a: &[f32; 4], b: &[f32; 4]| [0, 1, 2, 3].map(|i| b[i] - a[i]);
+        let dot = |a: [f32; 4], b: [f32; 4]| (0..4).map(|i| a[i] * b[i]).sum::<f32>();
+        // most different pair spans the line colors should lie on.
+        let (i, j) = [(0, 1), (1, 2), (2, 0)]
+            .into_iter()
+            .max_by(|(a0, a1), (b0, b1)| {
+                let da = diff(&rgba[*a0], &rgba[*a1]);
+                let db = diff(&rgba[*b0], &rgba[*b1]);
+                dot(da, da).total_cmp(&dot(db, db))
+            })
+            .unwrap();
+        let k = 3 - i - j;
+        let d = diff(&rgba[i], &rgba[j]);
+        let s_k = dot(diff(&rgba[i], &rgba[k]), d) / dot(d, d);
+        let off_line = (0..4).any(|n| (rgba[i][n] + s_k * d[n] - rgba[k][n]).abs() > 1.0);
+        if off_line {
+            return Self::Color(mean_color(&colors));
+        }
+        // s(p) = a.p + b, 0 at vertex i and 1 at vertex j.
+        let pos = vertices.map(|v| v.pos);
+        let (e1, e2) = (pos[j] - pos[i], pos[k] - pos[i]);
+        let det = e1.x * e2.y - e1.y * e2.x;
+        let a = Vec2::new(e2.y - s_k * e1.y, s_k * e1.x - e2.x) / det;
+        let b = -(a.x * pos[i].x + a.y * pos[i].y);
+        let mut s = [0.0; 3];
+        s[j] = 1.0;
+        s[k] = s_k;
+        let lo = (0..3).min_by(|x, y| s[*x].total_cmp(&s[*y])).unwrap();
+        let hi = (0..3).max_by(|x, y| s[*x].total_cmp(&s[*y])).unwrap();
+        // points on the gradient line through origin, same for every triangle of a strip.
+        let at = |s: f32| (a * (s - b) / a.length_sq()).to_pos2();
+        Self::Gradient([at(s[lo]), at(s[hi])], [colors[lo], colors[hi]])
+    }
+    /// affine map from texels to points. `None` when uv are on one line.
+    fn texture_mapping(vertices: [&Vertex; 3], size: [usize; 2]) -> Option<Self> {
+        let texel = vertices.map(|v| Pos2::new(v.uv.x * size[0] as f32, v.uv.y * size[1] as f32));
+        let pos = vertices.map(|v| v.pos);
+        let (t1, t2) = (texel[1] - texel[0], texel[2] - texel[0]);
+        let (p1, p2) = (pos[1] - pos[0], pos[2] - pos[0]);
+        let det = t1.x * t2.y - t1.y * t2.x;
+        if det.abs() < 1e-6 {
+            return None;
+        }
+        // points = m * texels + offset
+        let m = [
+            [
+                (p1.x * t2.y - p2.x * t1.y) / det,
+                (p2.x * t1.x - p1.x * t2.x) / det,
+            ],
+            [
+                (p1.y * t2.y - p2.y * t1.y) / det,
+                (p2.y * t1.x - p1.y * t2.x) / det,
+            ],
+        ];
+        let offset = Vec2::new(
+            pos[0].x - m[0][0] * texel[0].x - m[0][1] * texel[0].y,
+            pos[0].y - m[1][0] * texel[0].x - m[1][1] * texel[0].y,
+        );
+        let source = Rect::from_min_max(
+            texel[0].min(texel[1]).min(texel[2]),
+            texel[0].max(texel[1]).max(texel[2]),
+        );
+        Some(Self::Texture {
+            transform: [m[0][0], m[1][0], m[0][1], m[1][1], offset.x, offset.y],
+            source,
+            tint: mean_color(&vertices.map(|v| v.color)),
+        })
+    }
+    /// take `next` into this run if one canvas call can draw both.
+    fn merge(&mut self, next: &Self) -> bool {
+        let close = |a: Vec2, b: Vec2| (a - b).length_sq() < 1e-6;
+        match (self, next) {
+            (Self::Color(a), Self::Color(b)) => a == b,
+            (Self::Gradient(points, colors), Self::Gradient(next_points, next_colors)) => {
+                let same = |p: [Pos2; 2], c: [Color32; 2]| {
+                    c == *colors
+                        && close(p[0].to_vec2(), points[0].to_vec2())
+                        && close(p[1].to_vec2(), points[1].to_vec2())
+                };
+                let [p0, p1] = *next_points;
+                let [c0, c1] = *next_colors;
+                same([p0, p1], [c0, c1]) || same([p1, p0], [c1, c0])
+            }
+            (
+                Self::Texture {
+                    transform,
+                    source,
+                    tint,
+                },
+                Self::Texture {
+                    transform: next_transform,
+                    source: next_source,
+                    tint: next_tint,
+                },
+            ) => {
+                let same = tint == next_tint
+                    && transform
+                        .iter()
+                        .zip(next_transform)
+                        .all(|(a, b)| (a - b).abs() < 1e-3);
+                if same {
+                    *source = source.union(*next_source);
+                }
+                same
+            }
+            _ => false,
+        }
+    }
+    /// fill current path, which holds triangles of the run.
+    fn paint<C: Canvas2d>(&self, canvas: &mut C, texture_id: TextureId) {
+        match *self {
+            Self::Color(color) => {
+                canvas.set_fill_color(color);
+                canvas.fill();
+            }
+            Self::Gradient([from, to], colors) => canvas.fill_linear_gradient(
+                from.x as f64,
+                from.y as f64,
+                to.x as f64,
+                to.y as f64,
+                colors,
+            ),
+            Self::Texture {
+                transform,
+                source,
+                tint,
+            } => {
+                canvas.save();
+                canvas.clip();
+                let [a, b, c, d, e, f] = transform;
+                if a > 0.0 && d > 0.0 && b.abs() + c.abs() <= 1e-3 * (a + d) {
+                    let dest = Rect::from_min_max(
+                        Pos2::new(a * source.min.x + e, d * source.min.y + f),
+                        Pos2::new(a * source.max.x + e, d * source.max.y + f),
+                    );
+                    canvas.draw_texture_tinted(texture_id, source, dest, tint);
+                } else if !canvas.draw_texture_transformed(texture_id, source, transform, tint)
+                    && !TRANSFORM_WARNED.swap(true, Ordering::Relaxed)
+                {
+                    log::warn!("target can't draw rotated, flipped or sheared textures");
+                }
+                canvas.restore();
+            }
+        }
+    }
+}
+
+/// recognize mesh of `epaint::Shadow`: filled polygon with wide feathering around it.
+///
+/// returns polygon at the middle of the fade, width of the fade and color inside.
+fn shadow_outline(mesh: &Mesh) -> Option<(Vec<Pos2>, f32, Color32)> {
+    let vertices = &mesh.vertices;
+    let n = vertices.len() / 2;
+    if mesh.texture_id != TextureId::default()
+        || vertices.len() != 2 * n
+        || n < 3
+        || mesh.indices.len() != 3 * (n - 2) + 6 * n
+    {
+        return None;
+    }
+    // pairs of inner vertex with the color and outer transparent one.
+    let color = vertices[0].color;
+    let uv = vertices[0].uv;
+    let paired = vertices.chunks_exact(2).all(|pair| {
+        pair[0].color == color
+            && pair[1].color == Color32::TRANSPARENT
+            && pair[0].uv == uv
+            && pair[1].uv == uv
+    });
+    if color == Color32::TRANSPARENT || !paired {
+        return None;
+    }
+    let widths: Vec<f32> = vertices
+        .chunks_exact(2)
+        .map(|pair| pair[0].pos.distance(pair[1].pos))
+        .collect();
+    let fade = widths.iter().sum::<f32>() / n as f32;
+    let even = widths.iter().all(|w| (w - fade).abs() <= 0.01 * fade);
+    if fade <= 0.0 || !even {
+        return None;
+    }
+    let points = vertices
+        .chunks_exact(2)
+        .map(|pair| pair[0].pos + 0.5 * (pair[1].pos - pair[0].pos))
+        .collect();
+    Some((points, fade, color))
+}
+
+/// source rect in texels and dest rect of a mesh which is one axis aligned textured rectangle,
+/// as `egui::Image` makes.
+fn textured_quad(mesh: &Mesh, tex_size: [usize; 2]) -> Option<(Rect, Rect)> {
+    if mesh.vertices.len() != 4 || mesh.indices.len() != 6 {
+        return None;
+    }
+    let dest = Rect::from_points(&mesh.vertices.iter().map(|v| v.pos).collect::<Vec<_>>());
+    let uv = Rect::from_points(&mesh.vertices.iter().map(|v| v.uv).collect::<Vec<_>>());
+    if dest.width() <= 0.0 || dest.height() <= 0.0 || uv.width() <= 0.0 || uv.height() <= 0.0 {
+        return None;
+    }
+    // each vertex on the corner of dest matching its corner of uv, no two on the same corner.
+    let mut corners = [false; 4];
+    for v in &mesh.vertices {
+        let right = v.pos.x == dest.max.x;
+        let bottom = v.pos.y == dest.max.y;
+        let on_corner = (right || v.pos.x == dest.min.x) && (bottom || v.pos.y == dest.min.y);
+        let same_corner = right == (v.uv.x == uv.max.x) && bottom == (v.uv.y == uv.max.y);
+        let corner = &mut corners[right as usize * 2 + bottom as usize];
+        if !on_corner || !same_corner || *corner {
+            return None;
+        }
+        *corner = true;
+    }
+    // two triangles covering the rectangle once.
+    let area: f32 = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[t[i] as usize].pos);
+            ((b - a).x * (c - a).y - (b - a).y * (c - a).x).abs() / 2.0
+        })
+        .sum();
+    if (area - dest.area()).abs() > 1e-3 * dest.area() {
+        return None;
+    }
+    let size = epaint::vec2(tex_size[0] as f32, tex_size[1] as f32);
+    let source = Rect::from_min_max(
+        (uv.min.to_vec2() * size).to_pos2(),
+        (uv.max.to_vec2() * size).to_pos2(),
+    );
+    Some((source, dest))
+}
+
+/// paint mesh triangles. neighbours with same fill go into one path,
+/// so there are no anti aliasing seams inside them.
+///
+/// vertex colors are interpolated when they vary along one direction, as in feathering.
+/// textures are tinted by vertex color averaged over each triangle.
+pub fn paint_mesh<C: Canvas2d>(canvas: &mut C, mesh: &Mesh) {
+    if let TextureId::User(id) = mesh.texture_id {
+        let rect = Rect::from_points(&mesh.vertices.iter().map(|v| v.pos).collect::<Vec<_>>());
+        if canvas.paint_callback(id, rect) {
+            return;
+        }
+    }
+    let tex_size = canvas.texture_size(mesh.texture_id);
+    if let Some((source, dest)) = tex_size.and_then(|size| textured_quad(mesh, size)) {
+        let tint = mesh.vertices[0].color;
+        if mesh.vertices.iter().all(|v| v.color == tint) {
+            canvas.draw_texture_tinted(mesh.texture_id, source, dest, tint);
+            return;
+        }
+    }
+    let mut run: Option<TriangleFill> = None;
+    for triangle in mesh.indices.chunks_exact(3) {
+        let vertices = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+        let fill = match TriangleFill::of(vertices, tex_size) {
+            Some(fill) => fill,
+            None => continue,
+        };
+        let merged = match &mut run {
+            Some(run) => run.merge(&fill),
+            None => false,
+        };
+        if !merged {
+            if let Some(run) = run.take() {
+                run.paint(canvas, mesh.texture_id);
+            }
+            canvas.begin_path();
+            run = Some(fill);
+        }
+        let [a, b, c] = vertices.map(|v| v.pos);
+        canvas.move_to(a.x as f64, a.y as f64);
+        canvas.line_to(b.x as f64, b.y as f64);
+        canvas.line_to(c.x as f64, c.y as f64);
+        canvas.close_path();
+    }
+    if let Some(run) = run {
+        run.paint(canvas, mesh.texture_id);
+    }
+}
+
+/// shorter paths are painted as they are.
+const MIN_DECIMATED_POINTS: usize = 64;
+
+/// Douglas-Peucker simplification. dropped points lie within `tolerance` of the result.
+fn decimate(points: &[Pos2], tolerance: f32) -> Vec<Pos2> {
+    let mut keep = vec![false; points.len()];
+    let last = points.len() - 1;
+    keep[0] = true;
+    keep[last] = true;
+    // explicit stack, long paths would overflow recursion.
+    let mut spans = vec![(0, last)];
+    while let Some((first, last)) = spans.pop() {
+        let (a, b) = (points[first], points[last]);
+        let mut farthest = (tolerance * tolerance, None);
+        for (i, point) in points.iter().enumerate().take(last).skip(first + 1) {
+            let distance = segment_distance_sq(*point, a, b);
+            if distance > farthest.0 {
+                farthest = (distance, Some(i));
+            }
+        }
+        if let Some(i) = farthest.1 {
+            keep[i] = true;
+            spans.push((first, i));
+            spans.push((i, last));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+/// squared distance from `point` to segment `a`-`b`.
+fn segment_distance_sq(point: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let length_sq = ab.length_sq();
+    let t = if length_sq > 0.0 {
+        let ap = point - a;
+        ((ap.x * ab.x + ap.y * ab.y) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + t * ab - point).length_sq()
+}
+
+/// paint galley row by row, glyph by glyph.
+fn paint_text<C: Canvas2d>(canvas: &mut C, rendering_mode: TextRenderingMode, text: &TextShape) {
+    let TextShape {
+        pos,
+        galley,
+        underline,
+        override_text_color,
+        angle: _,
+    } = text;
+    let rows = &galley.rows;
+
+    let origin = pos;
+    for row in rows {
+        let row_rect = row.rect;
+        let bounds = row_rect
+            .translate(origin.to_vec2())
+            .expand(underline.width + 1.0);
+        if canvas.culls(bounds) {
+            continue;
+        }
+        match rendering_mode {
+            TextRenderingMode::EGUI => {
+                for glyph in row.glyphs.iter() {
+                    let Glyph {
+                        chr: _,
+                        pos,
+                        size: _,
+                        uv_rect,
+                        section_index: _,
+                    } = glyph;
+                    let offset = uv_rect.offset;
+                    let source = Rect::from_min_max(
+                        epaint::pos2(uv_rect.min[0] as f32, uv_rect.min[1] as f32),
+                        epaint::pos2(uv_rect.max[0] as f32, uv_rect.max[1] as f32),
+                    );
+                    let dest = Rect::from_min_size(
+                        epaint::pos2(pos.x + offset.x + origin.x, pos.y + offset.y + origin.y),
+                        uv_rect.size,
+                    );
+                    if !canvas.culls(dest) {
+                        canvas.draw_texture(TextureId::Managed(0), source, dest);
+                    }
+                }
+            }
+
+            TextRenderingMode::Browser => {
+                let row_text: String = row.glyphs.iter().map(|glyph| glyph.chr).collect();
+                let fill_style = override_text_color.unwrap_or(Color32::from_white_alpha(0xff));
+                let height = row_rect.height();
+                canvas.set_font(&format!("{}px serif", height));
+                canvas.set_fill_color(fill_style);
+                canvas.fill_text(
+                    &row_text,
+                    (row_rect.min.x + origin.x) as f64,
+                    (row_rect.max.y + origin.y) as f64,
+                    row_rect.width() as f64,
+                );
+            }
+        }
+        if *underline != Stroke::none() {
+            let lb = row_rect.left_bottom();
+            let rb = row_rect.right_bottom();
+            let line_segment = Shape::LineSegment {
+                points: [lb, rb],
+                stroke: *underline,
+            };
+            paint_shape(canvas, rendering_mode, &line_segment);
+        }
+    }
+}
+
+pub fn paint_shape<C: Canvas2d>(canvas: &mut C, rendering_mode: TextRenderingMode, shape: &Shape) {
+    match shape {
+        Shape::Noop => {}
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                if !canvas.culls(paint_bounds(shape)) {
+                    paint_shape(canvas, rendering_mode, shape);
+                }
+            }
+        }
+        Shape::Circle(circle) => {
+            let CircleShape {
+                center,
+                radius,
+                fill,
+                stroke,
+            } = circle;
+            let Stroke { width, color } = stroke;
+            canvas.begin_path();
+            canvas.set_line_width(*width as f64);
+            canvas.arc(
+                center.x as f64,
+                center.y as f64,
+                *radius as f64,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            );
+            canvas.set_stroke_color(*color);
+            canvas.set_fill_color(*fill);
+            canvas.stroke();
+            canvas.fill();
+        }
+        Shape::LineSegment { points, stroke } => {
+            canvas.begin_path();
+            canvas.move_to(points[0].x as f64, points[0].y as f64);
+            let Stroke { width, color } = stroke;
+            canvas.set_line_width(*width as f64);
+            canvas.set_stroke_color(*color);
+            canvas.line_to(points[1].x as f64, points[1].y as f64);
+            canvas.stroke();
+        }
+        Shape::Path(p) => {
+            let PathShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            } = p;
+
+            let decimated;
+            let points = match canvas.path_tolerance() {
+                Some(tolerance) if points.len() > MIN_DECIMATED_POINTS => {
+                    decimated = decimate(points, tolerance);
+                    &decimated
+                }
+                _ => points,
+            };
+            let Stroke { width, color } = stroke;
+            canvas.begin_path();
+            canvas.set_line_width(*width as f64);
+            canvas.set_stroke_color(*color);
+            if !points.is_empty() {
+                canvas.move_to(points[0].x as f64, points[0].y as f64);
+            }
+            for point in points.iter().skip(1) {
+                canvas.line_to(point.x as f64, point.y as f64);
+            }
+            if *closed {
+                canvas.set_fill_color(*fill);
+                canvas.close_path();
+                canvas.fill();
+            }
+            canvas.stroke();
+        }
+        Shape::Rect(rect) => {
+            let RectShape {
+                rect,
+                rounding,
+                fill,
+                stroke,
+            } = rect;
+            let Stroke { width, color } = stroke;
+            canvas.begin_path();
+            canvas.set_line_width(*width as f64);
+            canvas.set_stroke_color(*color);
+            canvas.set_fill_color(*fill);
+            //最初の角に移動する
+            let start_x = rect.min.x + rounding.nw;
+            let start_y = rect.min.y;
+            let next_x = rect.max.x - rounding.ne;
+            canvas.move_to(start_x as f64, start_y as f64);
+            canvas.line_to(next_x as f64, start_y as f64);
+            canvas.arc(
+                next_x as f64,
+                (rect.min.y + rounding.ne) as f64,
+                rounding.ne as f64,
+                -std::f64::consts::FRAC_PI_2,
+                0.0,
+            );
+            canvas.line_to(rect.max.x as f64, (rect.max.y - rounding.se) as f64);
+            canvas.arc(
+                (rect.max.x - rounding.se) as f64,
+                (rect.max.y - rounding.se) as f64,
+                rounding.se as f64,
+                0.0,
+                std::f64::consts::FRAC_PI_2,
+            );
+            canvas.line_to((rect.min.x + rounding.sw) as f64, rect.max.y as f64);
+            canvas.arc(
+                (rect.min.x + rounding.sw) as f64,
+                (rect.max.y - rounding.sw) as f64,
+                rounding.sw as f64,
+                std::f64::consts::FRAC_PI_2,
+                std::f64::consts::PI,
+            );
+            canvas.line_to(rect.min.x as f64, (rect.min.y + rounding.nw) as f64);
+            canvas.arc(
+                (rect.min.x + rounding.nw) as f64,
+                (rect.min.y + rounding.nw) as f64,
+                rounding.nw as f64,
+                std::f64::consts::PI,
+                3.0 * std::f64::consts::FRAC_PI_2,
+            );
+            // joins first corner to last instead of leaving both ends butt.
+            canvas.close_path();
+            canvas.fill();
+            canvas.stroke();
+        }
+        Shape::Text(text) => {
+            if !canvas.draw_cached_text(text, rendering_mode) {
+                paint_text(canvas, rendering_mode, text);
+            }
+        }
+
+        Shape::Mesh(mesh) => {
+            let drawn = match shadow_outline(mesh) {
+                Some((points, fade, color)) => canvas.fill_shadow(&points, fade as f64, color),
+                None => false,
+            };
+            if !drawn {
+                paint_mesh(canvas, mesh);
+            }
+        }
+        Shape::QuadraticBezier(qb) => {
+            let QuadraticBezierShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            } = qb;
+            let cp1 = points[1];
+            let end = points[2];
+            let Stroke { width, color } = stroke;
+            canvas.begin_path();
+            canvas.set_line_width(*width as f64);
+            canvas.set_stroke_color(*color);
+            canvas.move_to(points[0].x as f64, points[0].y as f64);
+            if *closed {
+                canvas.set_fill_color(*fill);
+            }
+            canvas.quadratic_curve_to(cp1.x as f64, cp1.y as f64, end.x as f64, end.y as f64);
+            if *closed {
+                canvas.close_path();
+                canvas.fill();
+            }
+            canvas.stroke();
+        }
+        Shape::CubicBezier(cb) => {
+            let CubicBezierShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            } = cb;
+            let cp1 = points[1];
+            let cp2 = points[2];
+            let end = points[3];
+            let Stroke { width, color } = stroke;
+            canvas.begin_path();
+            canvas.set_line_width(*width as f64);
+            canvas.set_stroke_color(*color);
+            canvas.move_to(points[0].x as f64, points[0].y as f64);
+            if *closed {
+                canvas.set_fill_color(*fill);
+            }
+            canvas.bezier_curve_to(
+                cp1.x as f64,
+                cp1.y as f64,
+                cp2.x as f64,
+                cp2.y as f64,
+                end.x as f64,
+                end.y as f64,
+            );
+            if *closed {
+                canvas.close_path();
+                canvas.fill();
+            }
+            canvas.stroke();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::{pos2, vec2, Shadow};
+
+    #[test]
+    fn window_shadow_is_recognized() {
+        let rect = Rect::from_min_size(pos2(10.0, 20.0), vec2(100.0, 50.0));
+        let shadow = Shadow::small_dark();
+        let (points, fade, color) = shadow_outline(&shadow.tessellate(rect, 4.0)).unwrap();
+        assert_eq!(color, shadow.color);
+        assert!((fade - shadow.extrusion).abs() < 0.1);
+        let outline = Rect::from_points(&points);
+        let expected = rect.expand(0.5 * shadow.extrusion);
+        assert!((outline.min - expected.min).length() < 1e-3);
+        assert!((outline.max - expected.max).length() < 1e-3);
+    }
+
+    #[test]
+    fn plain_mesh_is_not_shadow() {
+        let mut mesh = Mesh::default();
+        mesh.add_colored_rect(
+            Rect::from_min_size(pos2(0.0, 0.0), vec2(10.0, 10.0)),
+            Color32::RED,
+        );
+        assert!(shadow_outline(&mesh).is_none());
+    }
+
+    #[test]
+    fn image_is_one_quad() {
+        let mut mesh = Mesh::with_texture(TextureId::User(1));
+        let dest = Rect::from_min_size(pos2(4.0, 8.0), vec2(32.0, 16.0));
+        let uv = Rect::from_min_max(pos2(0.25, 0.0), pos2(0.75, 1.0));
+        mesh.add_rect_with_uv(dest, uv, Color32::WHITE);
+        let (source, quad_dest) = textured_quad(&mesh, [64, 32]).unwrap();
+        assert_eq!(
+            source,
+            Rect::from_min_max(pos2(16.0, 0.0), pos2(48.0, 32.0))
+        );
+        assert_eq!(quad_dest, dest);
+        // flipped image needs transform.
+        let uv0 = mesh.vertices[0].uv;
+        mesh.vertices[0].uv = mesh.vertices[1].uv;
+        mesh.vertices[1].uv = uv0;
+        assert!(textured_quad(&mesh, [64, 32]).is_none());
+    }
+
+    #[test]
+    fn flipped_uv_maps_texels_to_points() {
+        let vertex = |x: f32, y: f32, u: f32, v: f32| Vertex {
+            pos: pos2(x, y),
+            uv: pos2(u, v),
+            color: Color32::WHITE,
+        };
+        // mirrored horizontally, twice the texel size.
+        let (a, b, c) = (
+            vertex(0.0, 0.0, 1.0, 0.0),
+            vertex(32.0, 0.0, 0.0, 0.0),
+            vertex(0.0, 32.0, 1.0, 1.0),
+        );
+        let fill = TriangleFill::texture_mapping([&a, &b, &c], [16, 16]).unwrap();
+        let transform = match fill {
+            TriangleFill::Texture { transform, .. } => transform,
+            _ => unreachable!(),
+        };
+        assert_eq!(transform, [-2.0, 0.0, 0.0, 2.0, 32.0, 0.0]);
+    }
+
+    #[test]
+    fn tint_is_unmultiplied() {
+        assert_eq!(tint_rgb(Color32::WHITE), [255; 3]);
+        // faded white needs no copy, only alpha.
+        let faded = Color32::from_rgba_premultiplied(128, 128, 128, 128);
+        assert_eq!(tint_rgb(faded), [255; 3]);
+        let tint = Color32::from_rgba_premultiplied(64, 32, 0, 128);
+        assert_eq!(tint_rgb(tint), [128, 64, 0]);
+        // channels above alpha are not valid premultiplied colors.
+        let tint = Color32::from_rgba_premultiplied(200, 0, 0, 100);
+        assert_eq!(tint_rgb(tint), [255, 0, 0]);
+    }
+
+    /// counts clip state changes and fills.
+    #[derive(Default)]
+    struct ClipLog {
+        saves: usize,
+        clips: usize,
+        restores: usize,
+        fills: usize,
+        visible: Option<Rect>,
+        culled: usize,
+        /// ids with paint callback, and calls made.
+        callbacks: Vec<u64>,
+        called: Vec<(u64, Rect)>,
+    }
+
+    impl Canvas2d for ClipLog {
+        fn begin_path(&mut self) {}
+        fn close_path(&mut self) {}
+        fn move_to(&mut self, _x: f64, _y: f64) {}
+        fn line_to(&mut self, _x: f64, _y: f64) {}
+        fn arc(&mut self, _x: f64, _y: f64, _r: f64, _start: f64, _end: f64) {}
+        fn quadratic_curve_to(&mut self, _cpx: f64, _cpy: f64, _x: f64, _y: f64) {}
+        fn bezier_curve_to(&mut self, _: f64, _: f64, _: f64, _: f64, _: f64, _: f64) {}
+        fn rect(&mut self, _x: f64, _y: f64, _w: f64, _h: f64) {}
+        fn set_line_width(&mut self, _width: f64) {}
+        fn set_fill_color(&mut self, _color: Color32) {}
+        fn set_stroke_color(&mut self, _color: Color32) {}
+        fn fill(&mut self) {
+            self.fills += 1;
+        }
+        fn stroke(&mut self) {}
+        fn save(&mut self) {
+            self.saves += 1;
+        }
+        fn restore(&mut self) {
+            self.restores += 1;
+        }
+        fn clip(&mut self) {
+            self.clips += 1;
+        }
+        fn draw_texture(&mut self, _id: TextureId, _source: Rect, _dest: Rect) {}
+        fn set_font(&mut self, _font: &str) {}
+        fn fill_text(&mut self, _text: &str, _x: f64, _y: f64, _max_width: f64) {}
+        fn texture_size(&self, _id: TextureId) -> Option<[usize; 2]> {
+            None
+        }
+        fn culls(&mut self, bounds: Rect) -> bool {
+            let culled = matches!(self.visible, Some(visible) if !visible.intersects(bounds));
+            self.culled += culled as usize;
+            culled
+        }
+        fn paint_callback(&mut self, id: u64, rect: Rect) -> bool {
+            if !self.callbacks.contains(&id) {
+                return false;
+            }
+            self.called.push((id, rect));
+            true
+        }
+    }
+
+    #[test]
+    fn callback_marker_calls_callback() {
+        let rect = Rect::from_min_size(pos2(10.0, 20.0), vec2(30.0, 40.0));
+        let mut canvas = ClipLog {
+            callbacks: vec![7],
+            ..Default::default()
+        };
+        let shape = Shape::Vec(vec![
+            Shape::rect_filled(rect, 0.0, Color32::RED),
+            callback_shape(7, rect),
+            // not registered, painted as mesh of a missing texture.
+            callback_shape(8, rect),
+        ]);
+        paint_shape(&mut canvas, TextRenderingMode::EGUI, &shape);
+        assert_eq!(canvas.called, [(7, rect)]);
+        assert_eq!(canvas.fills, 1);
+    }
+
+    #[test]
+    fn clip_changes_only_with_clip_rect() {
+        let window = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 100.0));
+        let other = Rect::from_min_size(pos2(200.0, 0.0), vec2(100.0, 100.0));
+        let inside = Rect::from_min_size(pos2(10.0, 10.0), vec2(20.0, 20.0));
+        let across = Rect::from_min_size(pos2(90.0, 10.0), vec2(20.0, 20.0));
+        let mut canvas = ClipLog::default();
+        let mut clip = ClipState::default();
+        // inside own clip, no clip needed.
+        clip.apply(&mut canvas, window, inside);
+        assert_eq!(canvas.clips, 0);
+        // same clip rect is applied once.
+        clip.apply(&mut canvas, window, across);
+        clip.apply(&mut canvas, window, across);
+        clip.apply(&mut canvas, window, inside);
+        assert_eq!((canvas.saves, canvas.clips, canvas.restores), (1, 1, 0));
+        clip.apply(&mut canvas, other, other.expand(1.0));
+        assert_eq!((canvas.saves, canvas.clips, canvas.restores), (2, 2, 1));
+        clip.reset(&mut canvas);
+        assert_eq!(canvas.saves, canvas.restores);
+    }
+
+    #[test]
+    fn bounds_cover_stroke() {
+        let rect = Rect::from_min_size(pos2(10.0, 10.0), vec2(20.0, 20.0));
+        let stroke = Stroke::new(4.0, Color32::RED);
+        let bounds = paint_bounds(&Shape::rect_stroke(rect, 0.0, stroke));
+        assert!(bounds.contains_rect(rect.expand(2.0)));
+        let bounds = paint_bounds(&Shape::circle_stroke(pos2(0.0, 0.0), 10.0, stroke));
+        assert!(bounds.contains_rect(Rect::from_center_size(pos2(0.0, 0.0), vec2(24.0, 24.0))));
+    }
+
+    #[test]
+    fn shapes_outside_are_culled() {
+        let visible = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 100.0));
+        let mut canvas = ClipLog {
+            visible: Some(visible),
+            ..Default::default()
+        };
+        let shape = Shape::Vec(vec![
+            Shape::rect_filled(
+                Rect::from_min_size(pos2(10.0, 10.0), vec2(20.0, 20.0)),
+                0.0,
+                Color32::RED,
+            ),
+            Shape::rect_filled(
+                Rect::from_min_size(pos2(10.0, 500.0), vec2(20.0, 20.0)),
+                0.0,
+                Color32::RED,
+            ),
+        ]);
+        paint_shape(&mut canvas, TextRenderingMode::EGUI, &shape);
+        assert_eq!((canvas.fills, canvas.culled), (1, 1));
+    }
+
+    #[test]
+    fn dense_path_is_decimated() {
+        let points: Vec<Pos2> = (0..100_000)
+            .map(|i| {
+                let x = i as f32 * 0.01;
+                pos2(x, 50.0 * (x * 0.05).sin())
+            })
+            .collect();
+        let tolerance = 0.25;
+        let decimated = decimate(&points, tolerance);
+        assert!(decimated.len() < points.len() / 100);
+        assert_eq!(decimated.first(), points.first());
+        assert_eq!(decimated.last(), points.last());
+        for point in points.iter().step_by(97) {
+            let distance = decimated
+                .windows(2)
+                .map(|pair| segment_distance_sq(*point, pair[0], pair[1]))
+                .fold(f32::INFINITY, f32::min);
+            assert!(distance <= tolerance * tolerance * 1.01);
+        }
+    }
+}
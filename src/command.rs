@@ -0,0 +1,350 @@
+//! canvas calls of a frame encoded into one array, run by small js interpreter.
+//!
+//! each canvas call from wasm crosses into js. buffered, frame costs a few crossings
+//! per flush instead of one per call.
+use crate::texture::TextureFilter;
+use js_sys::{Array, Float32Array};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+#[wasm_bindgen(inline_js = r#"
+export function run_commands(c, a, text, images) {
+    const s = text.split("\0");
+    // state to go back to when a call throws, as later frames paint on this context.
+    // saves of this run are undone, transform set outside them is put back.
+    const t = c.getTransform();
+    let depth = 0;
+    let i = 0;
+    try {
+        while (i < a.length) {
+            switch (a[i++]) {
+                case 0: c.beginPath(); break;
+                case 1: c.closePath(); break;
+                case 2: c.moveTo(a[i++], a[i++]); break;
+                case 3: c.lineTo(a[i++], a[i++]); break;
+                case 4: c.arc(a[i++], a[i++], a[i++], a[i++], a[i++]); break;
+                case 5: c.quadraticCurveTo(a[i++], a[i++], a[i++], a[i++]); break;
+                case 6: c.bezierCurveTo(a[i++], a[i++], a[i++], a[i++], a[i++], a[i++]); break;
+                case 7: c.rect(a[i++], a[i++], a[i++], a[i++]); break;
+                case 8: c.lineWidth = a[i++]; break;
+                case 9: c.fillStyle = s[a[i++]]; break;
+                case 10: c.strokeStyle = s[a[i++]]; break;
+                case 11: c.fill(); break;
+                case 12: c.stroke(); break;
+                case 13: c.save(); depth++; break;
+                case 14: c.restore(); depth--; break;
+                case 15: c.clip(); break;
+                case 16: c.font = s[a[i++]]; break;
+                case 17: c.fillText(s[a[i++]], a[i++], a[i++], a[i++]); break;
+                case 18: c.globalAlpha = a[i++]; break;
+                case 19: c.drawImage(images[a[i++]], a[i++], a[i++], a[i++], a[i++], a[i++], a[i++], a[i++], a[i++]); break;
+                case 20: c.drawImage(images[a[i++]], a[i++], a[i++], a[i++], a[i++]); break;
+                case 21: c.translate(a[i++], a[i++]); break;
+                case 22:
+                    c.shadowOffsetX = a[i++];
+                    c.shadowOffsetY = a[i++];
+                    c.shadowBlur = a[i++];
+                    c.shadowColor = s[a[i++]];
+                    break;
+                case 23: {
+                    const g = c.createLinearGradient(a[i++], a[i++], a[i++], a[i++]);
+                    g.addColorStop(0, s[a[i++]]);
+                    g.addColorStop(1, s[a[i++]]);
+                    c.fillStyle = g;
+                    c.fill();
+                    break;
+                }
+                case 24: c.imageSmoothingEnabled = false; break;
+                case 25:
+                    c.imageSmoothingEnabled = true;
+                    c.imageSmoothingQuality = s[a[i++]];
+                    break;
+                case 26: c.transform(a[i++], a[i++], a[i++], a[i++], a[i++], a[i++]); break;
+                default: throw new Error("unknown canvas command " + a[i - 1]);
+            }
+        }
+    } catch (e) {
+        for (; depth > 0; depth--) c.restore();
+        c.setTransform(t);
+        throw e;
+    }
+}
+"#)]
+extern "C" {
+    #[wasm_bindgen(catch)]
+    fn run_commands(
+        context: &CanvasRenderingContext2d,
+        args: &Float32Array,
+        strings: &str,
+        images: &Array,
+    ) -> Result<(), JsValue>;
+}
+
+/// opcodes, numbered as in `run_commands`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Op {
+    BeginPath,
+    ClosePath,
+    MoveTo,
+    LineTo,
+    Arc,
+    QuadraticCurveTo,
+    BezierCurveTo,
+    Rect,
+    LineWidth,
+    FillStyle,
+    StrokeStyle,
+    Fill,
+    Stroke,
+    Save,
+    Restore,
+    Clip,
+    Font,
+    FillText,
+    GlobalAlpha,
+    DrawImage,
+    DrawImageScaled,
+    Translate,
+    Shadow,
+    FillLinearGradient,
+    SmoothingOff,
+    Smoothing,
+    Transform,
+}
+
+impl Op {
+    /// canvas calls the op stands for.
+    fn calls(self) -> usize {
+        match self {
+            Op::Shadow => 4,
+            Op::FillLinearGradient => 5,
+            Op::Smoothing => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// counts of last frame painted through command buffer.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct CommandStats {
+    /// canvas calls encoded.
+    pub ops: usize,
+    /// js calls made to run them, estimated. glue code of each call isn't counted.
+    pub calls: usize,
+}
+
+/// canvas calls waiting for [`Self::flush`].
+#[derive(Default)]
+pub(crate) struct CommandBuffer {
+    /// opcodes, each followed by its arguments.
+    args: Vec<f32>,
+    /// strings separated by nul.
+    strings: String,
+    string_ids: HashMap<String, u32>,
+    images: Vec<JsValue>,
+    pub stats: CommandStats,
+}
+
+impl CommandBuffer {
+    fn op(&mut self, op: Op, args: &[f32]) {
+        self.stats.ops += op.calls();
+        self.args.push(op as u8 as f32);
+        self.args.extend_from_slice(args);
+    }
+    /// index of `text` in string table, added once per flush.
+    fn string(&mut self, text: &str) -> f32 {
+        // nul would split the string in two. replaced before lookup, so ids stay one per entry.
+        let text: Cow<str> = if text.contains('\0') {
+            text.replace('\0', "\u{fffd}").into()
+        } else {
+            text.into()
+        };
+        if let Some(id) = self.string_ids.get(&*text) {
+            return *id as f32;
+        }
+        let id = self.string_ids.len() as u32;
+        if id > 0 {
+            self.strings.push('\0');
+        }
+        self.strings.push_str(&text);
+        self.string_ids.insert(text.into_owned(), id);
+        id as f32
+    }
+    fn image(&mut self, image: JsValue) -> f32 {
+        self.images.push(image);
+        (self.images.len() - 1) as f32
+    }
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+    /// run buffered calls on `context`. run which throws undoes its saves and transform.
+    pub fn flush(&mut self, context: &CanvasRenderingContext2d) {
+        if self.is_empty() {
+            return;
+        }
+        let args = Float32Array::from(&self.args[..]);
+        let images = Array::new();
+        for image in &self.images {
+            images.push(image);
+        }
+        if let Err(err) = run_commands(context, &args, &self.strings, &images) {
+            log::warn!("canvas commands failed: {:?}", err);
+        }
+        // array of args, array of images with a push per image, and run.
+        // estimate, as wasm-bindgen glue may call more.
+        self.stats.calls += 3 + self.images.len();
+        self.args.clear();
+        self.strings.clear();
+        self.string_ids.clear();
+        self.images.clear();
+    }
+
+    pub fn begin_path(&mut self) {
+        self.op(Op::BeginPath, &[]);
+    }
+    pub fn close_path(&mut self) {
+        self.op(Op::ClosePath, &[]);
+    }
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.op(Op::MoveTo, &[x as f32, y as f32]);
+    }
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.op(Op::LineTo, &[x as f32, y as f32]);
+    }
+    pub fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let args = [x, y, radius, start_angle, end_angle].map(|arg| arg as f32);
+        self.op(Op::Arc, &args);
+    }
+    pub fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        let args = [cpx, cpy, x, y].map(|arg| arg as f32);
+        self.op(Op::QuadraticCurveTo, &args);
+    }
+    pub fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        let args = [cp1x, cp1y, cp2x, cp2y, x, y].map(|arg| arg as f32);
+        self.op(Op::BezierCurveTo, &args);
+    }
+    pub fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.op(Op::Rect, &[x, y, w, h].map(|arg| arg as f32));
+    }
+    pub fn set_line_width(&mut self, width: f64) {
+        self.op(Op::LineWidth, &[width as f32]);
+    }
+    pub fn set_fill_style(&mut self, color: &str) {
+        let color = self.string(color);
+        self.op(Op::FillStyle, &[color]);
+    }
+    pub fn set_stroke_style(&mut self, color: &str) {
+        let color = self.string(color);
+        self.op(Op::StrokeStyle, &[color]);
+    }
+    pub fn fill(&mut self) {
+        self.op(Op::Fill, &[]);
+    }
+    pub fn stroke(&mut self) {
+        self.op(Op::Stroke, &[]);
+    }
+    pub fn save(&mut self) {
+        self.op(Op::Save, &[]);
+    }
+    pub fn restore(&mut self) {
+        self.op(Op::Restore, &[]);
+    }
+    pub fn clip(&mut self) {
+        self.op(Op::Clip, &[]);
+    }
+    pub fn set_font(&mut self, font: &str) {
+        let font = self.string(font);
+        self.op(Op::Font, &[font]);
+    }
+    pub fn fill_text(&mut self, text: &str, x: f64, y: f64, max_width: f64) {
+        let text = self.string(text);
+        self.op(Op::FillText, &[text, x as f32, y as f32, max_width as f32]);
+    }
+    pub fn set_global_alpha(&mut self, alpha: f64) {
+        self.op(Op::GlobalAlpha, &[alpha as f32]);
+    }
+    /// `source` and `dest` as x, y, width and height.
+    pub fn draw_image(&mut self, image: JsValue, source: [f64; 4], dest: [f64; 4]) {
+        let image = self.image(image);
+        let [sx, sy, sw, sh] = source.map(|arg| arg as f32);
+        let [dx, dy, dw, dh] = dest.map(|arg| arg as f32);
+        self.op(Op::DrawImage, &[image, sx, sy, sw, sh, dx, dy, dw, dh]);
+    }
+    /// whole image into `dest`.
+    pub fn draw_image_scaled(&mut self, image: JsValue, dest: [f64; 4]) {
+        let image = self.image(image);
+        let [dx, dy, dw, dh] = dest.map(|arg| arg as f32);
+        self.op(Op::DrawImageScaled, &[image, dx, dy, dw, dh]);
+    }
+    pub fn translate(&mut self, x: f64, y: f64) {
+        self.op(Op::Translate, &[x as f32, y as f32]);
+    }
+    pub fn transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        let args = [a, b, c, d, e, f].map(|arg| arg as f32);
+        self.op(Op::Transform, &args);
+    }
+    pub fn set_shadow(&mut self, offset_x: f64, offset_y: f64, blur: f64, color: &str) {
+        let color = self.string(color);
+        let args = [offset_x as f32, offset_y as f32, blur as f32, color];
+        self.op(Op::Shadow, &args);
+    }
+    /// fill current path with gradient from `from` at `(x0, y0)` to `to` at `(x1, y1)`.
+    pub fn fill_linear_gradient(&mut self, [x0, y0, x1, y1]: [f64; 4], [from, to]: [&str; 2]) {
+        let (from, to) = (self.string(from), self.string(to));
+        let [x0, y0, x1, y1] = [x0, y0, x1, y1].map(|arg| arg as f32);
+        self.op(Op::FillLinearGradient, &[x0, y0, x1, y1, from, to]);
+    }
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        match filter {
+            TextureFilter::Nearest => self.op(Op::SmoothingOff, &[]),
+            TextureFilter::Linear(quality) => {
+                let quality = self.string(quality.as_str());
+                self.op(Op::Smoothing, &[quality]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ops_and_strings_are_encoded() {
+        let mut commands = CommandBuffer::default();
+        commands.begin_path();
+        commands.move_to(1.0, 2.0);
+        commands.set_fill_style("red");
+        commands.fill_text("hi", 3.0, 4.0, 5.0);
+        commands.set_fill_style("red");
+        commands.set_shadow(0.0, 0.0, 2.0, "blue");
+        assert_eq!(
+            commands.args,
+            [
+                0.0, 2.0, 1.0, 2.0, 9.0, 0.0, 17.0, 1.0, 3.0, 4.0, 5.0, 9.0, 0.0, 22.0, 0.0, 0.0,
+                2.0, 2.0
+            ]
+        );
+        assert_eq!(commands.strings, "red\0hi\0blue");
+        assert_eq!(commands.stats.ops, 9);
+        assert_eq!(commands.stats.calls, 0);
+    }
+
+    #[test]
+    fn nul_does_not_split_strings() {
+        let mut commands = CommandBuffer::default();
+        commands.set_font("a");
+        commands.fill_text("b\0c", 0.0, 0.0, 1.0);
+        assert_eq!(commands.strings.split('\0').count(), 2);
+        // text already in table once replaced. next string must not take its id.
+        let mut commands = CommandBuffer::default();
+        commands.fill_text("b\u{fffd}c", 0.0, 0.0, 1.0);
+        commands.fill_text("b\0c", 0.0, 0.0, 1.0);
+        commands.fill_text("d", 0.0, 0.0, 1.0);
+        let ids: Vec<f32> = commands.args.chunks(5).map(|op| op[1]).collect();
+        assert_eq!(ids, [0.0, 0.0, 1.0]);
+        assert_eq!(commands.strings, "b\u{fffd}c\0d");
+    }
+}
@@ -0,0 +1,92 @@
+//! textures kept in RAM for painters without browser.
+use epaint::{textures::TexturesDelta, Color32, ColorImage, ImageData, ImageDelta, TextureId};
+use std::collections::HashMap;
+
+/// convert egui image into rgba image. font image becomes white with alpha.
+pub(crate) fn to_color_image(image: &ImageData) -> ColorImage {
+    match image {
+        ImageData::Color(color) => color.clone(),
+        ImageData::Alpha(alpha) => ColorImage {
+            size: alpha.size,
+            pixels: alpha
+                .pixels
+                .iter()
+                .map(|a| Color32::from_white_alpha(*a))
+                .collect(),
+        },
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CpuTextures {
+    images: HashMap<TextureId, ColorImage>,
+    /// freed after painting the frame which came with them.
+    pending_free: Vec<TextureId>,
+}
+
+impl CpuTextures {
+    pub fn get(&self, id: &TextureId) -> Option<&ColorImage> {
+        self.images.get(id)
+    }
+    pub fn set(&mut self, id: TextureId, delta: &ImageDelta) {
+        let image = to_color_image(&delta.image);
+        match delta.pos {
+            None => {
+                self.images.insert(id, image);
+            }
+            Some([x, y]) => {
+                let target = match self.images.get_mut(&id) {
+                    Some(target) => target,
+                    None => {
+                        log::warn!("partial update of unknown texture {:?}", id);
+                        return;
+                    }
+                };
+                let [w, h] = image.size;
+                let width = w.min(target.size[0].saturating_sub(x));
+                for row in 0..h.min(target.size[1].saturating_sub(y)) {
+                    let dst = (y + row) * target.size[0] + x;
+                    target.pixels[dst..dst + width]
+                        .copy_from_slice(&image.pixels[row * w..row * w + width]);
+                }
+            }
+        }
+    }
+    /// all live textures as one delta. replays this state into another painter.
+    #[cfg(all(feature = "webgl", target_arch = "wasm32"))]
+    pub fn full_delta(&self) -> TexturesDelta {
+        let mut textures_delta = TexturesDelta::default();
+        for (id, image) in &self.images {
+            textures_delta
+                .set
+                .insert(*id, ImageDelta::full(ImageData::Color(image.clone())));
+        }
+        textures_delta.free = self.pending_free.clone();
+        textures_delta
+    }
+    pub fn free(&mut self, id: TextureId) {
+        self.images.remove(&id);
+    }
+    /// apply delta of one frame.
+    ///
+    /// textures freed by this delta stay until next call.
+    pub fn update(&mut self, textures_delta: &TexturesDelta) {
+        for id in std::mem::take(&mut self.pending_free) {
+            self.free(id);
+        }
+        for (id, delta) in textures_delta.set.iter() {
+            self.set(*id, delta);
+        }
+        self.pending_free = textures_delta.free.clone();
+    }
+}
+
+/// png data url of premultiplied image.
+pub(crate) fn color_image_data_url(image: &ColorImage) -> String {
+    let mut rgba = Vec::with_capacity(image.pixels.len() * 4);
+    image
+        .pixels
+        .iter()
+        .for_each(|pixel| rgba.extend_from_slice(&pixel.to_srgba_unmultiplied()));
+    crate::png_data_url(image.size, rgba)
+}
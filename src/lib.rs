@@ -1,17 +1,63 @@
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn only_used_uploads_hold_frame() {
+        let used = HashSet::from([TextureId::default(), TextureId::User(1)]);
+        assert!(frame_ready(&used, |_| false));
+        assert!(!frame_ready(&used, |id| *id == TextureId::User(1)));
+        // image decoding for a later frame.
+        assert!(frame_ready(&used, |id| *id == TextureId::User(2)));
+    }
 }
 /// epaint to canvas api.
 pub struct Renderer {
     context: web_sys::CanvasRenderingContext2d,
-    textures: HashMap<TextureId, CanvasRenderingContext2d>,
+    textures: HashMap<TextureId, WebTexture>,
+    texture_options: HashMap<TextureId, TextureOptions>,
+    /// canvases of freed and evicted textures.
+    pool: CanvasPool,
+    /// pixels of textures evicted by memory budget.
+    evicted: HashMap<TextureId, web_sys::ImageData>,
+    memory_budget: Option<usize>,
+    frame_index: u64,
+    uploads: UploadHandlers,
+    upload_callback: Option<Rc<dyn Fn()>>,
+    hold_until_ready: bool,
+    use_image_bitmaps: bool,
     dpr: f64,
     rendering_mode: TextRenderingMode,
+    shape_rendering_mode: ShapeRenderingMode,
+    shadow_rendering_mode: ShadowRenderingMode,
+    paint_callbacks: HashMap<u64, PaintCallback>,
+    /// shapes, text rows and glyphs skipped as invisible since frame began.
+    culled: usize,
+    /// canvas calls of painting made right away since frame began.
+    direct_calls: usize,
+    path_decimation: bool,
+    text_cache: TextCache,
+    adaptive: Option<AdaptiveResolution>,
+    /// css width and height of canvas before adaptive resolution pinned them.
+    pinned_style: Option<[String; 2]>,
+    governor: ResolutionGovernor,
+    /// `performance.now()` at frame begin.
+    frame_start: f64,
+    /// visible canvas while frames are composed on `context` offscreen.
+    front: Option<CanvasRenderingContext2d>,
+    /// visible canvas size back buffer was presented at. other sizes come from integration.
+    presented_size: [u32; 2],
+    /// textures over canvas limits.
+    tiled: HashMap<TextureId, TiledTexture>,
+    limits: CanvasLimits,
+    /// canvas calls of frame, run by one js call. `None` calls canvas directly.
+    commands: Option<CommandBuffer>,
 }
 
 #[derive(Eq, PartialEq, Copy, Clone)]
@@ -32,17 +78,68 @@ pub enum TextRenderingMode {
     Browser,
 }
 
+/// how shapes become canvas calls.
+#[derive(Eq, PartialEq, Copy, Clone)]
+pub enum ShapeRenderingMode {
+    /// each shape as canvas path.
+    Canvas,
+    /// shapes through epaint tessellator, like other egui painters.
+    /// benefit
+    /// * feathering and shadows same as egui.
+    ///
+    /// limitation.
+    /// * slower. triangles are filled run by run.
+    /// * rotated textures are not drawn.
+    Tessellated,
+}
+
+/// how shadows of egui windows and popups are painted in [`ShapeRenderingMode::Canvas`].
+#[derive(Eq, PartialEq, Copy, Clone)]
+pub enum ShadowRenderingMode {
+    /// one polygon with canvas `shadowBlur`.
+    Blur,
+    /// feathered mesh from egui, triangle by triangle.
+    Mesh,
+    /// no shadows.
+    Skip,
+}
+
+use adaptive::{AdaptiveResolution, ResolutionGovernor};
+use canvas::{color_text, Canvas2d, ClipState, PaintCallback, WebCanvas};
+use command::{CommandBuffer, CommandStats};
 use epaint::{
-    text::Glyph, textures::TexturesDelta, CircleShape, Color32, CubicBezierShape, ImageData,
-    ImageDelta, Mesh, PathShape, QuadraticBezierShape,  RectShape, Shape, Stroke, TextShape,
-    TextureId,
+    tessellator::TessellationOptions, textures::TexturesDelta, vec2, ClippedMesh, ClippedShape,
+    Color32, ColorImage, ImageData, ImageDelta, Pos2, Rect, TextureId,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
+use std::rc::Rc;
+use text_cache::TextCache;
+use texture::{
+    CanvasLimits, CanvasPool, TextureMemory, TextureOptions, TiledTexture, UploadHandlers,
+    UploadTarget, WebTexture,
+};
 use wasm_bindgen::JsCast;
 use wasm_bindgen::__rt::IntoJsResult;
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement};
 
+pub mod adaptive;
+pub mod auto;
+pub mod canvas;
+pub mod command;
+mod cpu_texture;
+#[cfg(test)]
+mod parity;
+pub mod pdf;
+pub mod remote;
+#[cfg(target_arch = "wasm32")]
+pub mod runner;
+#[cfg(target_arch = "wasm32")]
+pub mod scheduler;
+pub mod svg;
+mod text_cache;
+pub mod texture;
+
 impl Renderer {
     /// set text rendering mode
     pub fn set_rendering_mode(&mut self, render_mode: TextRenderingMode) {
@@ -60,18 +157,43 @@ impl Renderer {
             .ok()??
             .dyn_into::<CanvasRenderingContext2d>()
             .ok()?;
-        context.set_image_smoothing_enabled(false);
-        let dpr = web_sys::window()
-            .map(|win| win.device_pixel_ratio())
-            .unwrap_or(1.0);
+        let limits = CanvasLimits::probe();
         let rect = canvas.get_bounding_client_rect();
-        canvas.set_width((rect.width() * dpr) as u32);
-        canvas.set_height((rect.height() * dpr) as u32);
+        let dpr = limits.clamp_scale([rect.width(), rect.height()], window_dpr());
+        if dpr < window_dpr() {
+            warn_clamped(dpr);
+        }
+        resize(&canvas, backing_size([rect.width(), rect.height()], dpr));
         Some(Self {
             context,
             textures: HashMap::new(),
+            texture_options: HashMap::new(),
+            pool: Default::default(),
+            evicted: HashMap::new(),
+            memory_budget: None,
+            frame_index: 0,
+            uploads: Default::default(),
+            upload_callback: None,
+            hold_until_ready: false,
+            use_image_bitmaps: false,
             dpr,
             rendering_mode: TextRenderingMode::EGUI,
+            shape_rendering_mode: ShapeRenderingMode::Canvas,
+            shadow_rendering_mode: ShadowRenderingMode::Blur,
+            paint_callbacks: HashMap::new(),
+            culled: 0,
+            direct_calls: 0,
+            path_decimation: false,
+            text_cache: Default::default(),
+            adaptive: None,
+            pinned_style: None,
+            governor: Default::default(),
+            frame_start: 0.0,
+            front: None,
+            presented_size: [0, 0],
+            tiled: HashMap::new(),
+            limits,
+            commands: None,
         })
     }
     pub fn new_with_canvas(canvas: &HtmlCanvasElement) -> Option<Self> {
@@ -80,517 +202,788 @@ impl Renderer {
             .ok()??
             .dyn_into::<CanvasRenderingContext2d>()
             .ok()?;
-        let dpr = web_sys::window()
-            .map(|win| win.device_pixel_ratio())
-            .unwrap_or(1.0);
+        let limits = CanvasLimits::probe();
         let rect = canvas.get_bounding_client_rect();
-        canvas.set_width((rect.width() * dpr) as u32);
-        canvas.set_height((rect.height() * dpr) as u32);
+        let dpr = limits.clamp_scale([rect.width(), rect.height()], window_dpr());
+        if dpr < window_dpr() {
+            warn_clamped(dpr);
+        }
+        resize(canvas, backing_size([rect.width(), rect.height()], dpr));
         Some(Self {
             context,
             textures: Default::default(),
+            texture_options: Default::default(),
+            pool: Default::default(),
+            evicted: Default::default(),
+            memory_budget: None,
+            frame_index: 0,
+            uploads: Default::default(),
+            upload_callback: None,
+            hold_until_ready: false,
+            use_image_bitmaps: false,
             dpr,
             rendering_mode: TextRenderingMode::EGUI,
+            shape_rendering_mode: ShapeRenderingMode::Canvas,
+            shadow_rendering_mode: ShadowRenderingMode::Blur,
+            paint_callbacks: HashMap::new(),
+            culled: 0,
+            direct_calls: 0,
+            path_decimation: false,
+            text_cache: Default::default(),
+            adaptive: None,
+            pinned_style: None,
+            governor: Default::default(),
+            frame_start: 0.0,
+            front: None,
+            presented_size: [0, 0],
+            tiled: HashMap::new(),
+            limits,
+            commands: None,
         })
     }
-    fn paint_shape(&mut self, shape: &epaint::Shape) {
-        match shape {
-            Shape::Noop => {}
-            Shape::Vec(shapes) => {
-                for shape in shapes {
-                    self.paint_shape(&shape);
-                }
-            }
-            Shape::Circle(circle) => {
-                let CircleShape {
-                    center,
-                    radius,
-                    fill,
-                    stroke,
-                } = circle;
-                let fill_color = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    fill.r(),
-                    fill.g(),
-                    fill.b(),
-                    fill.a()
-                );
-                let Stroke { width, color } = stroke;
-                let stroke_color = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context.begin_path();
-                self.context.set_line_width(*width as f64);
-                self.context
-                    .arc(
-                        center.x as f64,
-                        center.y as f64,
-                        *radius as f64,
-                        0.0,
-                        2.0 * std::f64::consts::PI,
-                    )
-                    .unwrap();
-                self.context
-                    .set_stroke_style(&stroke_color.into_js_result().unwrap());
-                self.context
-                    .set_fill_style(&fill_color.into_js_result().unwrap());
-                self.context.stroke();
-                self.context.fill();
+    /// set shape rendering mode
+    pub fn set_shape_rendering_mode(&mut self, mode: ShapeRenderingMode) {
+        self.shape_rendering_mode = mode;
+    }
+    /// simplify dense paths, as plot lines, to quarter device pixel before painting.
+    ///
+    /// off by default. shapes look the same, but canvas gets far fewer `lineTo` calls.
+    pub fn set_path_decimation(&mut self, decimate: bool) {
+        self.path_decimation = decimate;
+    }
+    /// memory for bitmaps of painted text, 16 MiB by default. 0 paints text glyph by glyph each frame.
+    ///
+    /// cached text is drawn at whole device pixels.
+    pub fn set_text_cache_budget(&mut self, bytes: usize) {
+        self.text_cache.set_budget(bytes);
+    }
+    /// encode canvas calls of each frame and run them with one js call, instead of calling
+    /// canvas from wasm one by one. off by default.
+    ///
+    /// paint callbacks and text cache misses flush calls buffered so far.
+    pub fn set_command_buffer(&mut self, enabled: bool) {
+        self.commands = if enabled {
+            Some(Default::default())
+        } else {
+            None
+        };
+    }
+    /// canvas calls and js calls of last frame, when command buffer is on.
+    pub fn command_stats(&self) -> Option<CommandStats> {
+        self.commands.as_ref().map(|commands| commands.stats)
+    }
+    /// canvas calls painting made right away in last frame, each a js call.
+    /// with command buffer, those it doesn't take, as paint callbacks.
+    pub fn direct_calls(&self) -> usize {
+        self.direct_calls
+    }
+    /// render below device pixel ratio while painting is over budget. `None` always renders at full ratio.
+    ///
+    /// css size of canvas is pinned while on, so layout stays the same as backing store
+    /// shrinks. it is restored when turned off.
+    pub fn set_adaptive_resolution(&mut self, adaptive: Option<AdaptiveResolution>) {
+        self.adaptive = adaptive;
+        let canvas = self.visible_canvas();
+        if adaptive.is_some() && self.pinned_style.is_none() {
+            self.pinned_style = canvas.map(|canvas| pin_css_size(&canvas));
+        } else if adaptive.is_none() {
+            self.governor = Default::default();
+            if let (Some(canvas), Some(style)) = (canvas, self.pinned_style.take()) {
+                restore_css_size(&canvas, style);
             }
-            Shape::LineSegment { points, stroke } => {
-                self.context.begin_path();
-                self.context.move_to(points[0].x as f64, points[0].y as f64);
-                let Stroke { width, color } = stroke;
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context.set_line_width(*width as f64);
-                self.context
-                    .set_stroke_style(&color_text.into_js_result().unwrap());
-                self.context.line_to(points[1].x as f64, points[1].y as f64);
-                self.context.stroke();
+        }
+    }
+    /// fraction of device pixel ratio painted at. below 1 only with adaptive resolution.
+    pub fn render_scale(&self) -> f64 {
+        self.governor.scale()
+    }
+    /// compose frames on offscreen canvas and show them with one `drawImage` when complete.
+    ///
+    /// frames with textures still uploading, and frames which fail midway, are not shown.
+    /// previous frame stays on screen, as with [`Self::set_hold_until_ready`].
+    pub fn set_back_buffer(&mut self, enabled: bool) {
+        if enabled == self.front.is_some() {
+            return;
+        }
+        if enabled {
+            let canvas = self.context.canvas().unwrap();
+            let back = texture::create_context(canvas.width(), canvas.height());
+            self.presented_size = [canvas.width(), canvas.height()];
+            self.front = Some(std::mem::replace(&mut self.context, back));
+        } else if let Some(front) = self.front.take() {
+            let back = std::mem::replace(&mut self.context, front);
+            // resize not presented yet.
+            if let (Some(front), Some(back)) = (self.context.canvas(), back.canvas()) {
+                resize(&front, [back.width(), back.height()]);
             }
-            Shape::Path(p) => {
-                let PathShape {
-                    points,
-                    closed,
-                    fill,
-                    stroke,
-                } = p;
-
-                let Stroke { width, color } = stroke;
-                self.context.begin_path();
-                self.context.set_line_width(*width as f64);
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context
-                    .set_stroke_style(&color_text.into_js_result().unwrap());
-                if !points.is_empty() {
-                    self.context.move_to(points[0].x as f64, points[0].y as f64);
-                }
-                for point in points.iter().skip(1) {
-                    self.context.line_to(point.x as f64, point.y as f64);
+            texture::release(&back);
+        }
+    }
+    /// copy back buffer to visible canvas. frames of [`Self::paint_and_update_texture`]
+    /// are presented by it, call this after painting with [`Self::paint`].
+    ///
+    /// visible canvas takes size of back buffer here, as resizing clears it.
+    pub fn present(&mut self) {
+        let front = match &self.front {
+            Some(front) => front,
+            None => return,
+        };
+        if let (Some(canvas), Some(back)) = (front.canvas(), self.context.canvas()) {
+            resize(&canvas, [back.width(), back.height()]);
+            self.presented_size = [back.width(), back.height()];
+        }
+        // copy replaces transparent parts too.
+        front.set_global_composite_operation("copy").unwrap();
+        front
+            .draw_image_with_html_canvas_element(&self.context.canvas().unwrap(), 0.0, 0.0)
+            .unwrap();
+        front.set_global_composite_operation("source-over").unwrap();
+    }
+    /// set shadow rendering mode
+    pub fn set_shadow_rendering_mode(&mut self, mode: ShadowRenderingMode) {
+        self.shadow_rendering_mode = mode;
+    }
+    /// draw with `callback` where [`canvas::callback_shape`] of `id` is painted.
+    pub fn set_paint_callback(
+        &mut self,
+        id: u64,
+        callback: impl FnMut(&CanvasRenderingContext2d, Rect) + 'static,
+    ) {
+        self.paint_callbacks.insert(id, Box::new(callback));
+    }
+    pub fn remove_paint_callback(&mut self, id: u64) {
+        self.paint_callbacks.remove(&id);
+    }
+    /// shapes, text rows and glyphs skipped in last frame, being outside clip rect or canvas.
+    pub fn culled(&self) -> usize {
+        self.culled
+    }
+    pub fn paint(&mut self, shape: &epaint::ClippedShape) {
+        let mut used = HashSet::new();
+        texture::collect_texture_ids(&shape.1, &mut used);
+        self.use_textures(&used);
+        let viewport = self.viewport();
+        let rendering_mode = self.rendering_mode;
+        let shape_rendering_mode = self.shape_rendering_mode;
+        let options = TessellationOptions::from_pixels_per_point(self.dpr as f32);
+        let mut canvas = self.web_canvas();
+        canvas.visible = shape.0.intersect(viewport);
+        if !canvas.culls(canvas::paint_bounds(&shape.1)) {
+            match shape_rendering_mode {
+                ShapeRenderingMode::Canvas => {
+                    canvas::paint_clipped(&mut canvas, rendering_mode, shape)
                 }
-                if *closed {
-                    let color_text = format!(
-                        "#{:02x}{:02x}{:02x}{:02x}",
-                        fill.r(),
-                        fill.g(),
-                        fill.b(),
-                        fill.a()
-                    );
-                    self.context
-                        .set_fill_style(&color_text.into_js_result().unwrap());
-                    self.context.close_path();
-                    self.context.fill();
+                ShapeRenderingMode::Tessellated => {
+                    canvas::paint_clipped_tessellated(&mut canvas, rendering_mode, options, shape)
                 }
-                self.context.stroke();
             }
-            Shape::Rect(rect) => {
-                let RectShape {
-                    rect,
-                    rounding,
-                    fill,
-                    stroke,
-                } = rect;
-                let Stroke { width, color } = stroke;
-                self.context.begin_path();
-                self.context.set_line_width(*width as f64);
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context
-                    .set_stroke_style(&color_text.into_js_result().unwrap());
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    fill.r(),
-                    fill.g(),
-                    fill.b(),
-                    fill.a()
-                );
-                self.context
-                    .set_fill_style(&color_text.into_js_result().unwrap());
-                //最初の角に移動する
-                let start_x = rect.min.x + rounding.nw;
-                let start_y = rect.min.y;
-                let next_x = rect.max.x - rounding.ne;
-                self.context.move_to(start_x as f64, start_y as f64);
-                self.context.line_to(next_x as f64, start_y as f64);
-                self.context
-                    .arc(
-                        next_x as f64,
-                        (rect.min.y + rounding.ne) as f64,
-                        rounding.ne as f64,
-                        -std::f64::consts::FRAC_PI_2,
-                        0.0,
-                    )
-                    .unwrap();
-                self.context
-                    .line_to(rect.max.x as f64, (rect.max.y - rounding.se) as f64);
-                self.context
-                    .arc(
-                        (rect.max.x - rounding.se) as f64,
-                        (rect.max.y - rounding.se) as f64,
-                        rounding.se as f64,
-                        0.0,
-                        std::f64::consts::FRAC_PI_2,
-                    )
-                    .unwrap();
-                self.context
-                    .line_to((rect.min.x + rounding.sw) as f64, rect.max.y as f64);
-                self.context
-                    .arc(
-                        (rect.min.x + rounding.sw) as f64,
-                        (rect.max.y - rounding.sw) as f64,
-                        rounding.sw as f64,
-                        std::f64::consts::FRAC_PI_2,
-                        std::f64::consts::PI,
-                    )
-                    .unwrap();
-                self.context
-                    .line_to(rect.min.x as f64, (rect.min.y + rounding.nw) as f64);
-                self.context
-                    .arc(
-                        (rect.min.x + rounding.nw) as f64,
-                        (rect.min.y + rounding.nw) as f64,
-                        rounding.nw as f64,
-                        std::f64::consts::PI,
-                        3.0 * std::f64::consts::FRAC_PI_2,
-                    )
-                    .unwrap();
-
-                self.context.fill();
-                self.context.stroke();
-            }
-            Shape::Text(text) => {
-                let TextShape {
-                    pos,
-                    galley,
-                    underline,
-                    override_text_color,
-                    angle: _,
-                } = text;
-                let rows = &galley.rows;
-
-                let origin = pos;
-                for row in rows {
-                    let row_rect = row.rect;
-                    match self.rendering_mode {
-                        TextRenderingMode::EGUI => {
-                            for glyph in row.glyphs.iter() {
-                                let Glyph {
-                                    chr: _,
-                                    pos,
-                                    size: _,
-                                    uv_rect,
-                                    section_index: _,
-                                } = glyph;
-                                let offset = uv_rect.offset;
-                                let source_top_left = uv_rect.min;
-                                let sx = source_top_left[0] as f64;
-                                let sy = source_top_left[1] as f64;
-
-                                let source_bottom_right = uv_rect.max;
-                                let sw = source_bottom_right[0] as f64 - sx;
-                                let sh = source_bottom_right[1] as f64 - sy;
-                                let dx = (pos.x + offset.x + origin.x) as f64;
-                                let dy = (pos.y + offset.y + origin.y) as f64;
-                                let dw = uv_rect.size.x as f64;
-                                let dh = uv_rect.size.y as f64;
-                                self.context.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                                    &self.textures.get(&TextureId::Managed(0)).unwrap().canvas().unwrap(),
-                                    sx,
-                                    sy,
-                                    sw,
-                                    sh,
-                                    dx,
-                                    dy,
-                                    dw,
-                                    dh,
-                                ).unwrap();
-                            }
-                        }
-
-                        TextRenderingMode::Browser => {
-                            let row_text: String =
-                                row.glyphs.iter().map(|glyph| glyph.chr).collect();
-                            let fill_style =
-                                override_text_color.unwrap_or(Color32::from_white_alpha(0xff));
-                            let fill_style = format!(
-                                "#{:02x}{:02x}{:02x}{:02x}",
-                                fill_style.r(),
-                                fill_style.g(),
-                                fill_style.b(),
-                                fill_style.a()
-                            )
-                            .into_js_result()
-                            .unwrap();
-                            let height=row_rect.height();
-                            self.context.set_font(&format!("{}px serif",height));
-                            self.context.set_fill_style(&fill_style);
-                            self.context.fill_text_with_max_width(
-                                &row_text,
-                                (row_rect.min.x + origin.x) as f64,
-                                (row_rect.max.y + origin.y) as f64,
-                                row_rect.width() as f64
-                            ).unwrap();
-                        }
-                    }
-                    if *underline != Stroke::none() {
-                        let lb = row_rect.left_bottom();
-                        let rb = row_rect.right_bottom();
-                        let line_segment = Shape::LineSegment {
-                            points: [lb, rb],
-                            stroke: *underline,
-                        };
-                        self.paint_shape(&line_segment);
-                    }
-                }
-            }
-
-            Shape::Mesh(mesh) => {
-                let Mesh {
-                    indices,
-                    vertices,
-                    texture_id:_,
-                } = mesh;
-                for triangle in indices.chunks(3) {
-                    self.context.begin_path();
-                    let vert1 = vertices[triangle[0] as usize];
-                    let vert2 = vertices[triangle[1] as usize];
-                    let vert3 = vertices[triangle[2] as usize];
-                    self.context.move_to(vert1.pos.x as f64, vert1.pos.y as f64);
-                    self.context.line_to(vert2.pos.x as f64, vert2.pos.y as f64);
-                    self.context.line_to(vert3.pos.x as f64, vert3.pos.y as f64);
-                    self.context.close_path();
-                    self.context.fill();
-                }
-                log::warn!("mesh is not supported.")
+        }
+        canvas.flush();
+        let (culled, direct_calls) = (canvas.culled, canvas.direct_calls);
+        self.culled += culled;
+        self.direct_calls += direct_calls;
+    }
+    pub fn paint_and_update_texture(
+        &mut self,
+        shapes: &[epaint::ClippedShape],
+        textures_delta: TexturesDelta,
+    ) {
+        let TexturesDelta { set, free } = textures_delta;
+        let mut used = HashSet::new();
+        for shape in shapes {
+            texture::collect_texture_ids(&shape.1, &mut used);
+        }
+        self.begin_frame(set, &used);
+        let viewport = self.viewport();
+        let rendering_mode = self.rendering_mode;
+        let shape_rendering_mode = self.shape_rendering_mode;
+        let options = TessellationOptions::from_pixels_per_point(self.dpr as f32);
+        let mut canvas = self.web_canvas();
+        let mut clip = ClipState::default();
+        for ClippedShape(clip_rect, shape) in shapes {
+            canvas.visible = clip_rect.intersect(viewport);
+            let bounds = canvas::paint_bounds(shape);
+            if canvas.culls(bounds) {
+                continue;
             }
-            Shape::QuadraticBezier(qb) => {
-                let QuadraticBezierShape {
-                    points,
-                    closed,
-                    fill,
-                    stroke,
-                } = qb;
-                let cp1 = points[1];
-                let end = points[2];
-                let Stroke { width, color } = stroke;
-                self.context.begin_path();
-                self.context.set_line_width(*width as f64);
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context
-                    .set_stroke_style(&color_text.into_js_result().unwrap());
-                self.context.move_to(points[0].x as f64, points[0].y as f64);
-                if *closed {
-                    let color_text = format!(
-                        "#{:02x}{:02x}{:02x}{:02x}",
-                        fill.r(),
-                        fill.g(),
-                        fill.b(),
-                        fill.a()
-                    );
-                    self.context
-                        .set_fill_style(&color_text.into_js_result().unwrap());
+            clip.apply(&mut canvas, *clip_rect, bounds);
+            match shape_rendering_mode {
+                ShapeRenderingMode::Canvas => {
+                    canvas::paint_shape(&mut canvas, rendering_mode, shape)
                 }
-                self.context.quadratic_curve_to(
-                    cp1.x as f64,
-                    cp1.y as f64,
-                    end.x as f64,
-                    end.y as f64,
-                );
-                if *closed {
-                    self.context.close_path();
-                    self.context.fill();
+                ShapeRenderingMode::Tessellated => {
+                    canvas::paint_tessellated(&mut canvas, rendering_mode, options, shape)
                 }
-                self.context.stroke();
             }
-            Shape::CubicBezier(cb) => {
-                let CubicBezierShape {
-                    points,
-                    closed,
-                    fill,
-                    stroke,
-                } = cb;
-                let cp1 = points[1];
-                let cp2 = points[2];
-                let end = points[3];
-                let Stroke { width, color } = stroke;
-                self.context.begin_path();
-                self.context.set_line_width(*width as f64);
-                let color_text = format!(
-                    "#{:02x}{:02x}{:02x}{:02x}",
-                    color.r(),
-                    color.g(),
-                    color.b(),
-                    color.a()
-                );
-                self.context
-                    .set_stroke_style(&color_text.into_js_result().unwrap());
-                self.context.move_to(points[0].x as f64, points[0].y as f64);
-                if *closed {
-                    let color_text = format!(
-                        "#{:02x}{:02x}{:02x}{:02x}",
-                        fill.r(),
-                        fill.g(),
-                        fill.b(),
-                        fill.a()
-                    );
-                    self.context
-                        .set_fill_style(&color_text.into_js_result().unwrap());
-                }
-                self.context.bezier_curve_to(
-                    cp1.x as f64,
-                    cp1.y as f64,
-                    cp2.x as f64,
-                    cp2.y as f64,
-                    end.x as f64,
-                    end.y as f64,
-                );
-                if *closed {
-                    self.context.close_path();
-                    self.context.fill();
-                }
-                self.context.stroke();
+        }
+        clip.reset(&mut canvas);
+        canvas.flush();
+        let (culled, direct_calls) = (canvas.culled, canvas.direct_calls);
+        self.culled += culled;
+        self.direct_calls += direct_calls;
+        self.end_frame(free, &used);
+    }
+    /// paint meshes already tessellated by epaint, as other egui painters take them.
+    ///
+    /// glyphs come from font atlas, so text needs [`TextRenderingMode::EGUI`].
+    pub fn paint_meshes(&mut self, clipped_meshes: &[ClippedMesh], textures_delta: TexturesDelta) {
+        let TexturesDelta { set, free } = textures_delta;
+        let used = clipped_meshes
+            .iter()
+            .map(|mesh| mesh.1.texture_id)
+            .collect();
+        self.begin_frame(set, &used);
+        let viewport = self.viewport();
+        let mut canvas = self.web_canvas();
+        let mut clip = ClipState::default();
+        for ClippedMesh(clip_rect, mesh) in clipped_meshes {
+            canvas.visible = clip_rect.intersect(viewport);
+            let bounds = mesh.calc_bounds().expand(1.0);
+            if canvas.culls(bounds) {
+                continue;
             }
+            clip.apply(&mut canvas, *clip_rect, bounds);
+            canvas::paint_mesh(&mut canvas, mesh);
         }
+        clip.reset(&mut canvas);
+        canvas.flush();
+        let (culled, direct_calls) = (canvas.culled, canvas.direct_calls);
+        self.culled += culled;
+        self.direct_calls += direct_calls;
+        self.end_frame(free, &used);
     }
-    pub fn paint(&mut self, shape: &epaint::ClippedShape) {
-        // create clip rectangle.
-        let rect = shape.0;
-        self.context.begin_path();
-        self.context.save();
-        self.context.rect(
-            rect.min.x as f64,
-            rect.min.y as f64,
-            rect.width() as f64,
-            rect.height() as f64,
-        );
-        self.context.clip();
-        self.paint_shape(&shape.1);
-        self.context.restore();
+    fn web_canvas(&mut self) -> WebCanvas<'_> {
+        WebCanvas {
+            context: &self.context,
+            textures: &self.textures,
+            tiled: &self.tiled,
+            texture_options: &self.texture_options,
+            callbacks: &mut self.paint_callbacks,
+            shadows: self.shadow_rendering_mode,
+            dpr: self.dpr,
+            visible: Rect::EVERYTHING,
+            culled: 0,
+            path_tolerance: self.path_decimation.then(|| 0.25 / self.dpr as f32),
+            text_cache: Some(&mut self.text_cache),
+            commands: self.commands.as_mut(),
+            direct_calls: 0,
+        }
     }
-    pub fn paint_and_update_texture(
+    /// canvas area in points.
+    fn viewport(&self) -> Rect {
+        match self.context.canvas() {
+            Some(canvas) => Rect::from_min_size(
+                Pos2::ZERO,
+                vec2(canvas.width() as f32, canvas.height() as f32) / self.dpr as f32,
+            ),
+            None => Rect::EVERYTHING,
+        }
+    }
+    /// upload textures, bring back `used` ones and scale canvas for painting.
+    fn begin_frame(
         &mut self,
-        shapes: &[epaint::ClippedShape],
-        textures_delta: TexturesDelta,
+        set: impl IntoIterator<Item = (TextureId, ImageDelta)>,
+        used: &HashSet<TextureId>,
     ) {
-        let TexturesDelta { set, free } = textures_delta;
+        self.frame_start = now_ms();
+        self.uploads.collect();
+        self.culled = 0;
+        self.direct_calls = 0;
+        if let Some(commands) = &mut self.commands {
+            commands.stats = Default::default();
+        }
         for (id, delta) in set {
             self.set_texture(id, delta);
         }
-        let dpr = web_sys::window()
-            .map(|win| win.device_pixel_ratio())
-            .unwrap_or(1.0);
+        self.frame_index += 1;
+        self.use_textures(used);
+        self.update_scale();
+        self.context.scale(self.dpr, self.dpr).unwrap();
+    }
+    /// bring back evicted `used` textures and mark them used this frame.
+    fn use_textures(&mut self, used: &HashSet<TextureId>) {
+        for id in used {
+            self.restore_texture(*id);
+            if let Some(texture) = self.textures.get_mut(id) {
+                texture.last_used = self.frame_index;
+            }
+        }
+    }
+    /// follow device pixel ratio and adaptive render scale. resizing clears canvas.
+    fn update_scale(&mut self) {
+        let canvas = match self.visible_canvas() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let rect = canvas.get_bounding_client_rect();
+        let dpr = self.surface_scale([rect.width(), rect.height()]);
         if (dpr - self.dpr).abs() > 0.01 {
+            if dpr < window_dpr() * self.governor.scale() {
+                warn_clamped(dpr);
+            }
             self.dpr = dpr;
-            if let Some(canvas) = self.context.canvas() {
-                let rect = canvas.get_bounding_client_rect();
-                canvas.set_width((rect.width() * dpr) as u32);
-                canvas.set_height((rect.height() * dpr) as u32);
+            self.resize_surface(backing_size([rect.width(), rect.height()], dpr));
+        } else if self.front.is_some() {
+            // back buffer follows visible canvas, which integrations resize too.
+            let size = [canvas.width(), canvas.height()];
+            if size != self.presented_size {
+                self.presented_size = size;
+                self.resize_surface(size);
             }
         }
-        self.context.scale(self.dpr, self.dpr).unwrap();
-        for shape in shapes {
-            self.paint(shape);
+    }
+    /// resize canvas painted into. with back buffer, visible canvas keeps previous frame
+    /// and takes new size when next frame is presented.
+    pub(crate) fn resize_surface(&self, size: [u32; 2]) {
+        if let Some(canvas) = self.context.canvas() {
+            resize(&canvas, size);
         }
+    }
+    /// pixels per point of canvas of `css_size`, lowered to fit canvas limits.
+    pub(crate) fn surface_scale(&self, css_size: [f64; 2]) -> f64 {
+        self.limits
+            .clamp_scale(css_size, window_dpr() * self.governor.scale())
+    }
+    /// backing store size of canvas of `css_size` at [`Self::surface_scale`].
+    pub(crate) fn surface_size(&self, css_size: [f64; 2]) -> [u32; 2] {
+        backing_size(css_size, self.surface_scale(css_size))
+    }
+    /// limits found at creation. textures over them are split into tiles.
+    pub fn canvas_limits(&self) -> CanvasLimits {
+        self.limits
+    }
+    /// replace probed limits, to test tiling or for a browser probing misses.
+    ///
+    /// applies to textures uploaded afterwards.
+    pub fn set_canvas_limits(&mut self, limits: CanvasLimits) {
+        self.limits = limits;
+    }
+    /// canvas on page. with back buffer, `context` paints offscreen.
+    pub(crate) fn visible_canvas(&self) -> Option<HtmlCanvasElement> {
+        self.front.as_ref().unwrap_or(&self.context).canvas()
+    }
+    fn end_frame(&mut self, free: Vec<TextureId>, used: &HashSet<TextureId>) {
         for id in free {
             self.free_texture(id);
         }
         self.context.scale(1.0 / self.dpr, 1.0 / self.dpr).unwrap();
+        self.enforce_memory_budget(used);
+        if let Some(adaptive) = &self.adaptive {
+            self.governor.record(now_ms() - self.frame_start, adaptive);
+        }
+        if !self.textures_pending(used) {
+            self.present();
+        }
+    }
+    /// clear canvas with `clear_color` and paint one egui frame.
+    ///
+    /// returns platform output and `needs_repaint`, which are left to the integration.
+    pub fn render_full_output(
+        &mut self,
+        clear_color: &Color32,
+        full_output: egui::FullOutput,
+    ) -> (egui::PlatformOutput, bool) {
+        let egui::FullOutput {
+            platform_output,
+            needs_repaint,
+            textures_delta,
+            shapes,
+        } = full_output;
+        let TexturesDelta { set, free } = textures_delta;
+        for (id, delta) in set {
+            self.set_texture(id, delta);
+        }
+        if self.hold_until_ready && !self.textures_ready(&shapes) {
+            // keep previous frame on screen. upload callback asks for next one.
+            for id in free {
+                self.free_texture(id);
+            }
+            return (platform_output, needs_repaint);
+        }
+        self.clear(clear_color);
+        self.paint_and_update_texture(
+            &shapes,
+            TexturesDelta {
+                set: Default::default(),
+                free,
+            },
+        );
+        (platform_output, needs_repaint)
+    }
+    /// images still decoding. textures are drawn blank until they are done.
+    pub fn pending_uploads(&self) -> usize {
+        self.uploads.pending()
+    }
+    /// called each time a texture upload finishes. hook repaint signal of your app here.
+    pub fn set_upload_callback(&mut self, callback: impl Fn() + 'static) {
+        self.upload_callback = Some(Rc::new(callback));
+    }
+    /// don't present frames of [`Self::render_full_output`] whose textures are still uploading.
+    ///
+    /// previous frame stays on screen instead. use with [`Self::set_upload_callback`],
+    /// otherwise nothing repaints when uploads finish.
+    ///
+    /// applies to [`Self::render_full_output`] only, which clears canvas itself.
+    /// callers of [`Self::paint_and_update_texture`] have cleared canvas already,
+    /// so there is no previous frame left to keep. [`Self::set_back_buffer`] holds those frames.
+    pub fn set_hold_until_ready(&mut self, hold: bool) {
+        self.hold_until_ready = hold;
+    }
+    fn textures_ready(&self, shapes: &[epaint::ClippedShape]) -> bool {
+        let mut used = HashSet::new();
+        for shape in shapes {
+            texture::collect_texture_ids(&shape.1, &mut used);
+        }
+        !self.textures_pending(&used)
+    }
+    fn textures_pending(&self, used: &HashSet<TextureId>) -> bool {
+        !frame_ready(used, |id| {
+            matches!(self.textures.get(id), Some(texture) if texture.uploads.pending() > 0)
+                || matches!(self.tiled.get(id), Some(tiled) if tiled.pending())
+        })
     }
-    pub fn clear(&self, color: &Color32) {
+    pub fn clear(&mut self, color: &Color32) {
+        // resize before clearing, not in the middle of frame.
+        self.update_scale();
         let canvas = self.context.canvas().unwrap();
         let width = canvas.width();
         let height = canvas.height();
         self.context.rect(0.0, 0.0, width as f64, height as f64);
-        let color_text = format!(
-            "#{:02x}{:02x}{:02x}{:02x}",
-            color.r(),
-            color.g(),
-            color.b(),
-            color.a()
-        );
         self.context
-            .set_fill_style(&color_text.into_js_result().unwrap());
+            .set_fill_style(&color_text(color).into_js_result().unwrap());
         self.context.fill();
     }
+    /// read back rendered pixels inside `rect`.
+    ///
+    /// `rect` is in points. returned image is in physical pixels, so its size is `rect * dpr`.
+    /// part of `rect` outside of canvas is cut off.
+    pub fn read_pixels(&self, rect: Rect) -> Option<ColorImage> {
+        let canvas = self.context.canvas()?;
+        let bounds = Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(canvas.width() as f32, canvas.height() as f32),
+        );
+        let rect = Rect::from_min_max(
+            (rect.min.to_vec2() * self.dpr as f32).round().to_pos2(),
+            (rect.max.to_vec2() * self.dpr as f32).round().to_pos2(),
+        )
+        .intersect(bounds);
+        if rect.width() <= 0.0 || rect.height() <= 0.0 {
+            return None;
+        }
+        let image_data = self
+            .context
+            .get_image_data(
+                rect.min.x as f64,
+                rect.min.y as f64,
+                rect.width() as f64,
+                rect.height() as f64,
+            )
+            .ok()?;
+        let size = [image_data.width() as usize, image_data.height() as usize];
+        Some(ColorImage::from_rgba_unmultiplied(size, &image_data.data()))
+    }
 
     pub fn set_texture(&mut self, id: TextureId, image_delta: ImageDelta) -> Option<()> {
         let ImageDelta { image, pos } = image_delta;
         if !(id == TextureId::default() && self.rendering_mode == TextRenderingMode::Browser) {
             let (w, h) = (image.width(), image.height());
             let sub_image = upload_texture(image);
-            // get or create canvas
-            let ctx = self.textures.entry(id).or_insert_with(|| {
-                let canvas: HtmlCanvasElement = web_sys::window()
-                    .unwrap()
-                    .document()
-                    .unwrap()
-                    .create_element("canvas")
-                    .unwrap()
-                    .unchecked_into();
-                canvas.set_width(w as u32);
-                canvas.set_height(h as u32);
-                canvas
-                    .get_context("2d")
-                    .unwrap()
-                    .unwrap()
-                    .dyn_into::<CanvasRenderingContext2d>()
-                    .unwrap()
-            });
+            let mipmaps = self.texture_options(id).mipmaps;
+            match pos {
+                // partial update needs old pixels.
+                Some(_) => self.restore_texture(id),
+                None => {
+                    if id == TextureId::default() {
+                        // new font atlas, glyphs moved.
+                        self.text_cache.clear();
+                    }
+                    self.evicted.remove(&id);
+                    // whole image replaced. keep canvas only when size matches.
+                    if let Some(texture) = self.textures.remove(&id) {
+                        if texture.size() == [w as u32, h as u32] {
+                            self.textures.insert(id, texture);
+                        } else {
+                            texture.recycle(&mut self.pool);
+                        }
+                    }
+                    if let Some(tiled) = self.tiled.remove(&id) {
+                        if tiled.size == [w as u32, h as u32] {
+                            self.tiled.insert(id, tiled);
+                        } else {
+                            tiled.recycle(&mut self.pool);
+                        }
+                    }
+                    if !self.limits.fits(w as u32, h as u32) && !self.tiled.contains_key(&id) {
+                        let tiled = TiledTexture::new(
+                            [w as u32, h as u32],
+                            self.limits.tile_side(),
+                            &mut self.pool,
+                            mipmaps,
+                        );
+                        self.tiled.insert(id, tiled);
+                    }
+                }
+            }
+            let replace = pos.is_none();
             let pos = pos.unwrap_or([0, 0]);
+            let targets: Vec<UploadTarget> = match self.tiled.get(&id) {
+                Some(tiled) => {
+                    let update = Rect::from_min_size(
+                        epaint::pos2(pos[0] as f32, pos[1] as f32),
+                        vec2(w as f32, h as f32),
+                    );
+                    tiled
+                        .tiles
+                        .iter()
+                        .filter(|(rect, _)| rect.intersects(update))
+                        .map(|(rect, tile)| {
+                            let pos = [
+                                pos[0] as f64 - rect.min.x as f64,
+                                pos[1] as f64 - rect.min.y as f64,
+                            ];
+                            UploadTarget::new(tile, pos, replace)
+                        })
+                        .collect()
+                }
+                None => {
+                    // get or create canvas
+                    let pool = &mut self.pool;
+                    let texture = self
+                        .textures
+                        .entry(id)
+                        .or_insert_with(|| WebTexture::new(pool.take(w as u32, h as u32), mipmaps));
+                    vec![UploadTarget::new(
+                        texture,
+                        [pos[0] as f64, pos[1] as f64],
+                        replace,
+                    )]
+                }
+            };
+            let use_image_bitmaps = self.use_image_bitmaps;
+            let callback = self.upload_callback.clone();
+            let sub_image_c = sub_image.clone();
+            self.uploads.add(&sub_image, move |loaded| {
+                let image = if loaded {
+                    Some(&sub_image_c)
+                } else {
+                    log::warn!("failed to decode texture {:?}", id);
+                    None
+                };
+                for target in &targets {
+                    target.finish(image, use_image_bitmaps);
+                }
+                if let Some(callback) = &callback {
+                    callback();
+                }
+            });
+        }
 
-            {
-                let ctx_c = ctx.clone();
-                let sub_image_c = sub_image.clone();
-                let onload_handler = wasm_bindgen::closure::Closure::wrap(Box::new(move || {
-                    ctx_c
-                        .draw_image_with_html_image_element(
-                            &sub_image_c,
-                            pos[0] as f64,
-                            pos[1] as f64,
-                        )
-                        .unwrap();
-                })
-                    as Box<dyn FnMut()>);
+        Some(())
+    }
 
-                sub_image.set_onload(Some(onload_handler.as_ref().unchecked_ref()));
-                onload_handler.forget();
+    pub fn free_texture(&mut self, id: TextureId) {
+        if let Some(texture) = self.textures.remove(&id) {
+            texture.recycle(&mut self.pool);
+        }
+        if let Some(tiled) = self.tiled.remove(&id) {
+            tiled.recycle(&mut self.pool);
+        }
+        self.evicted.remove(&id);
+        self.texture_options.remove(&id);
+    }
+    /// limit canvas memory of textures to `bytes`. `None` is unlimited.
+    ///
+    /// over budget, least recently drawn textures are evicted after painting.
+    /// their pixels stay in RAM and go back to a canvas when drawn again.
+    /// font atlas and textures drawn in current frame are never evicted.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.memory_budget = bytes;
+        self.enforce_memory_budget(&HashSet::new());
+    }
+    pub fn texture_memory(&self) -> TextureMemory {
+        TextureMemory {
+            resident: self.textures.values().map(WebTexture::bytes).sum::<usize>()
+                + self.tiled.values().map(TiledTexture::bytes).sum::<usize>(),
+            pooled: self.pool.bytes(),
+            evicted: self
+                .evicted
+                .values()
+                .map(|data| data.width() as usize * data.height() as usize * 4)
+                .sum(),
+            budget: self.memory_budget,
+        }
+    }
+    fn enforce_memory_budget(&mut self, in_use: &HashSet<TextureId>) {
+        let budget = match self.memory_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        // tiled textures are never evicted, but take their part of budget.
+        let tiled: usize = self.tiled.values().map(TiledTexture::bytes).sum();
+        let mut resident = tiled + self.textures.values().map(WebTexture::bytes).sum::<usize>();
+        // pooled canvases go first.
+        self.pool.shrink_to(budget.saturating_sub(resident));
+        while resident + self.pool.bytes() > budget {
+            let victim = self
+                .textures
+                .iter()
+                .filter(|(id, texture)| {
+                    **id != TextureId::default()
+                        && !in_use.contains(id)
+                        && texture.uploads.pending() == 0
+                })
+                .min_by_key(|(_, texture)| texture.last_used)
+                .map(|(id, _)| *id);
+            match victim {
+                Some(id) => {
+                    self.evict_texture(id);
+                    self.pool.shrink_to(0);
+                    resident = tiled + self.textures.values().map(WebTexture::bytes).sum::<usize>();
+                }
+                None => break,
+            }
+        }
+    }
+    /// move texture pixels into RAM and give its canvas back.
+    fn evict_texture(&mut self, id: TextureId) {
+        if let Some(texture) = self.textures.remove(&id) {
+            let [w, h] = texture.size();
+            match texture.context.get_image_data(0.0, 0.0, w as f64, h as f64) {
+                Ok(data) => {
+                    self.evicted.insert(id, data);
+                }
+                Err(err) => log::warn!("failed to evict texture {:?}: {:?}", id, err),
             }
+            texture.recycle(&mut self.pool);
         }
+    }
+    /// bring evicted texture back to a canvas.
+    fn restore_texture(&mut self, id: TextureId) {
+        if let Some(data) = self.evicted.remove(&id) {
+            let context = self.pool.take(data.width(), data.height());
+            if context.put_image_data(&data, 0.0, 0.0).is_err() {
+                log::warn!("failed to restore texture {:?}", id);
+            }
+            let texture = WebTexture::new(context, self.texture_options(id).mipmaps);
+            if texture.mipmaps.get() {
+                *texture.levels.borrow_mut() = texture::build_levels(&texture.context);
+            }
+            if self.use_image_bitmaps {
+                texture.snapshot.capture(&texture.context.canvas().unwrap());
+            }
+            self.textures.insert(id, texture);
+        }
+    }
+    /// draw textures from `ImageBitmap` copies, which browsers may keep decoded on GPU.
+    ///
+    /// copy is made when uploads of a texture finish. until it is ready,
+    /// and after each update of the texture, canvas of the texture is drawn.
+    pub fn set_use_image_bitmaps(&mut self, use_image_bitmaps: bool) {
+        self.use_image_bitmaps = use_image_bitmaps;
+        let tiles = self.tiled.values().flat_map(|tiled| &tiled.tiles);
+        for texture in self.textures.values().chain(tiles.map(|(_, tile)| tile)) {
+            if use_image_bitmaps && texture.uploads.pending() == 0 {
+                texture.snapshot.capture(&texture.context.canvas().unwrap());
+            } else {
+                texture.snapshot.invalidate();
+            }
+        }
+    }
+    /// set how texture `id` is sampled. can be called before the texture is uploaded.
+    pub fn set_texture_options(&mut self, id: TextureId, options: TextureOptions) {
+        self.texture_options.insert(id, options);
+        let tiles = self
+            .tiled
+            .get(&id)
+            .into_iter()
+            .flat_map(|tiled| &tiled.tiles);
+        for texture in self
+            .textures
+            .get(&id)
+            .into_iter()
+            .chain(tiles.map(|(_, tile)| tile))
+        {
+            texture.mipmaps.set(options.mipmaps);
+            *texture.levels.borrow_mut() = if options.mipmaps {
+                texture::build_levels(&texture.context)
+            } else {
+                Vec::new()
+            };
+        }
+    }
+    pub fn texture_options(&self, id: TextureId) -> TextureOptions {
+        self.texture_options
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| TextureOptions::default_for(id))
+    }
+}
+pub(crate) fn window_dpr() -> f64 {
+    web_sys::window()
+        .map(|win| win.device_pixel_ratio())
+        .unwrap_or(1.0)
+}
 
-        Some(())
+/// pixels of canvas of `css_size` at `scale`. all canvas sizing goes through this,
+/// so sizes agree and canvas is not cleared twice.
+fn backing_size(css_size: [f64; 2], scale: f64) -> [u32; 2] {
+    [
+        (css_size[0] * scale).round() as u32,
+        (css_size[1] * scale).round() as u32,
+    ]
+}
+
+/// set css size of `canvas` to its current size. returns css width and height set before.
+fn pin_css_size(canvas: &HtmlCanvasElement) -> [String; 2] {
+    let style = canvas.style();
+    let rect = canvas.get_bounding_client_rect();
+    let previous =
+        ["width", "height"].map(|name| style.get_property_value(name).unwrap_or_default());
+    let _ = style.set_property("width", &format!("{}px", rect.width()));
+    let _ = style.set_property("height", &format!("{}px", rect.height()));
+    previous
+}
+
+fn restore_css_size(canvas: &HtmlCanvasElement, previous: [String; 2]) {
+    let style = canvas.style();
+    for (name, value) in ["width", "height"].into_iter().zip(previous) {
+        let _ = if value.is_empty() {
+            style.remove_property(name).map(|_| ())
+        } else {
+            style.set_property(name, &value)
+        };
     }
+}
 
-    pub fn free_texture(&mut self, id: TextureId) {
-        self.textures.remove(&id);
+/// set backing store size when it differs. setting it clears canvas even when same.
+fn resize(canvas: &HtmlCanvasElement, size: [u32; 2]) {
+    if canvas.width() != size[0] || canvas.height() != size[1] {
+        canvas.set_width(size[0]);
+        canvas.set_height(size[1]);
     }
 }
+
+fn warn_clamped(dpr: f64) {
+    log::warn!(
+        "canvas is over browser size limits, painted at {:.2} pixels per point",
+        dpr
+    );
+}
+
+/// true when none of `used` textures is `pending` upload. textures uploading for later frames
+/// don't hold this one back.
+fn frame_ready(used: &HashSet<TextureId>, pending: impl Fn(&TextureId) -> bool) -> bool {
+    !used.iter().any(pending)
+}
+
+fn now_ms() -> f64 {
+    web_sys::window()
+        .and_then(|win| win.performance())
+        .map(|performance| performance.now())
+        .unwrap_or(0.0)
+}
+
 /// convert egui image into HtmlImageElement.
 ///
 /// using data url.
@@ -615,8 +1008,16 @@ fn upload_texture(image: epaint::ImageData) -> HtmlImageElement {
     }
     // fill buffer by each pixels
     log::debug!("uploading image");
+    // we upload pixels by data url.
+    let image = web_sys::HtmlImageElement::new().unwrap();
+    image.set_src(&png_data_url(size, buffer));
+
+    image
+}
+/// encode rgba pixels into png data url.
+pub(crate) fn png_data_url(size: [usize; 2], rgba: Vec<u8>) -> String {
     // create
-    let rgba_image = image::RgbaImage::from_raw(size[0] as u32, size[1] as u32, buffer).unwrap();
+    let rgba_image = image::RgbaImage::from_raw(size[0] as u32, size[1] as u32, rgba).unwrap();
     let mut output_buffer = Vec::new();
 
     rgba_image
@@ -627,10 +1028,5 @@ fn upload_texture(image: epaint::ImageData) -> HtmlImageElement {
         .unwrap();
 
     let image_in_base64 = base64::encode(output_buffer);
-    // we upload pixels by data url.
-    let image = web_sys::HtmlImageElement::new().unwrap();
-    let data_url = format!("data:image/png;base64,{}", image_in_base64);
-    image.set_src(&data_url);
-
-    image
+    format!("data:image/png;base64,{}", image_in_base64)
 }
@@ -0,0 +1,791 @@
+//! visual parity of [`paint_shape`](crate::canvas::paint_shape) with epaint's own tessellator.
+//!
+//! each shape is rendered twice on transparent background.
+//! * tessellated by [`Tessellator`] and rasterized like GPU painters do.
+//! * translated by [`paint_clipped`] onto [`SkiaCanvas`], a software canvas behaving like browser one.
+//!
+//! then pixels of both are compared shape by shape.
+use crate::canvas::{
+    arc_to_cubics, gradient_stops, paint_clipped, paint_clipped_tessellated, Canvas2d,
+};
+use crate::{ShapeRenderingMode, TextRenderingMode};
+use epaint::{
+    tessellator::{TessellationOptions, Tessellator},
+    ClippedShape, Color32, ColorImage, Mesh, Pos2, Rect, TextureId,
+};
+use std::collections::HashMap;
+use tiny_skia::{
+    ClipMask, Color, FillRule, FilterQuality, GradientStop, LinearGradient, Paint, PathBuilder,
+    Pattern, Pixmap, Point, Shader, SpreadMode, Stroke, Transform,
+};
+
+/// how much two renderings may differ.
+#[derive(Clone, Copy, Debug)]
+pub struct Tolerance {
+    /// channel difference up to this is ignored. anti aliasing differs a little on every edge.
+    pub channel: u8,
+    /// pixels allowed to differ more than `channel`.
+    pub pixels: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShapeDiff {
+    /// index in compared shapes.
+    pub index: usize,
+    pub max_channel_diff: u8,
+    /// pixels differing more than `Tolerance::channel`.
+    pub differing_pixels: usize,
+}
+
+impl ShapeDiff {
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        self.differing_pixels <= tolerance.pixels
+    }
+}
+
+pub struct Harness {
+    /// in pixels.
+    pub size: [usize; 2],
+    pub pixels_per_point: f32,
+    /// path of [`Self::canvas`].
+    pub shape_rendering_mode: ShapeRenderingMode,
+    textures: HashMap<TextureId, ColorImage>,
+}
+
+impl Harness {
+    pub fn new(size: [usize; 2], pixels_per_point: f32) -> Self {
+        Self {
+            size,
+            pixels_per_point,
+            shape_rendering_mode: ShapeRenderingMode::Canvas,
+            textures: HashMap::new(),
+        }
+    }
+    /// texture used by both paths. font atlas is `TextureId::default()`.
+    pub fn set_texture(&mut self, id: TextureId, image: ColorImage) {
+        self.textures.insert(id, image);
+    }
+    /// render each shape both ways and measure difference.
+    pub fn compare(&self, shapes: &[ClippedShape], tolerance: &Tolerance) -> Vec<ShapeDiff> {
+        shapes
+            .iter()
+            .enumerate()
+            .map(|(index, shape)| {
+                let reference = self.tessellated(shape);
+                let canvas = self.canvas(shape);
+                let mut diff = ShapeDiff {
+                    index,
+                    max_channel_diff: 0,
+                    differing_pixels: 0,
+                };
+                for (a, b) in reference.pixels.iter().zip(canvas.pixels.iter()) {
+                    let channel_diff = (0..4)
+                        .map(|i| (a[i] as i16 - b[i] as i16).unsigned_abs() as u8)
+                        .max()
+                        .unwrap();
+                    diff.max_channel_diff = diff.max_channel_diff.max(channel_diff);
+                    if channel_diff > tolerance.channel {
+                        diff.differing_pixels += 1;
+                    }
+                }
+                diff
+            })
+            .collect()
+    }
+    /// shape through epaint tessellator.
+    pub fn tessellated(&self, shape: &ClippedShape) -> ColorImage {
+        let ClippedShape(clip_rect, shape) = shape.clone();
+        let tex_size = self
+            .textures
+            .get(&TextureId::default())
+            .map_or([1, 1], |image| image.size);
+        let mut tessellator = Tessellator::from_options(
+            TessellationOptions::from_pixels_per_point(self.pixels_per_point),
+        );
+        let mut mesh = Mesh::default();
+        tessellator.tessellate_shape(tex_size, shape, &mut mesh);
+        let mut image = ColorImage::new(self.size, Color32::TRANSPARENT);
+        self.rasterize(&mesh, clip_rect, &mut image);
+        image
+    }
+    /// shape through [`Canvas2d`] translation, text as egui glyphs.
+    pub fn canvas(&self, shape: &ClippedShape) -> ColorImage {
+        let textures = self
+            .textures
+            .iter()
+            .map(|(id, image)| (*id, to_pixmap(image)))
+            .collect();
+        let mut canvas = SkiaCanvas::new(self.size, self.pixels_per_point, &textures);
+        match self.shape_rendering_mode {
+            ShapeRenderingMode::Canvas => {
+                paint_clipped(&mut canvas, TextRenderingMode::EGUI, shape)
+            }
+            ShapeRenderingMode::Tessellated => paint_clipped_tessellated(
+                &mut canvas,
+                TextRenderingMode::EGUI,
+                TessellationOptions::from_pixels_per_point(self.pixels_per_point),
+                shape,
+            ),
+        }
+        let data = canvas.pixmap.data();
+        ColorImage {
+            size: self.size,
+            pixels: data
+                .chunks_exact(4)
+                .map(|p| Color32::from_rgba_premultiplied(p[0], p[1], p[2], p[3]))
+                .collect(),
+        }
+    }
+    /// triangles with interpolated vertex colors. pixel centers are sampled and
+    /// edges follow top-left rule, so adjacent triangles never cover a pixel twice.
+    fn rasterize(&self, mesh: &Mesh, clip_rect: Rect, image: &mut ColorImage) {
+        let ppp = self.pixels_per_point;
+        let texture = self.textures.get(&mesh.texture_id);
+        let [width, height] = self.size;
+        let clip = Rect::from_min_max(
+            (clip_rect.min.to_vec2() * ppp).to_pos2(),
+            (clip_rect.max.to_vec2() * ppp).to_pos2(),
+        )
+        .intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            epaint::vec2(width as f32, height as f32),
+        ));
+        if clip.width() <= 0.0 || clip.height() <= 0.0 {
+            return;
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let mut v = [
+                mesh.vertices[triangle[0] as usize],
+                mesh.vertices[triangle[1] as usize],
+                mesh.vertices[triangle[2] as usize],
+            ];
+            v.iter_mut()
+                .for_each(|v| v.pos = (v.pos.to_vec2() * ppp).to_pos2());
+            let mut area = edge(v[0].pos, v[1].pos, v[2].pos);
+            if area == 0.0 {
+                continue;
+            }
+            if area < 0.0 {
+                v.swap(1, 2);
+                area = -area;
+            }
+            let min = v[0].pos.min(v[1].pos).min(v[2].pos).max(clip.min);
+            let max = v[0].pos.max(v[1].pos).max(v[2].pos).min(clip.max);
+            for y in min.y.floor() as usize..max.y.ceil() as usize {
+                for x in min.x.floor() as usize..max.x.ceil() as usize {
+                    let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    if !clip.contains(p) {
+                        continue;
+                    }
+                    let w = [
+                        edge(v[1].pos, v[2].pos, p),
+                        edge(v[2].pos, v[0].pos, p),
+                        edge(v[0].pos, v[1].pos, p),
+                    ];
+                    let inside = (0..3).all(|i| {
+                        w[i] > 0.0
+                            || (w[i] == 0.0 && top_left(v[(i + 1) % 3].pos, v[(i + 2) % 3].pos))
+                    });
+                    if !inside {
+                        continue;
+                    }
+                    let mut color = [0.0f32; 4];
+                    let mut uv = epaint::Vec2::ZERO;
+                    for i in 0..3 {
+                        let weight = w[i] / area;
+                        for (c, channel) in color.iter_mut().zip(v[i].color.to_array()) {
+                            *c += weight * channel as f32;
+                        }
+                        uv += weight * v[i].uv.to_vec2();
+                    }
+                    if let Some(texture) = texture {
+                        let tx =
+                            ((uv.x * texture.size[0] as f32) as usize).min(texture.size[0] - 1);
+                        let ty =
+                            ((uv.y * texture.size[1] as f32) as usize).min(texture.size[1] - 1);
+                        let texel = texture[(tx, ty)].to_array();
+                        for (c, t) in color.iter_mut().zip(texel) {
+                            *c = *c * t as f32 / 255.0;
+                        }
+                    }
+                    let dst = &mut image.pixels[y * width + x];
+                    *dst = blend(*dst, color);
+                }
+            }
+        }
+    }
+}
+
+/// twice signed area of `a b p`.
+///
+/// evaluated from the same end whichever way the edge runs, so `edge(a, b, p) == -edge(b, a, p)`
+/// exactly. otherwise rounding can put a pixel outside of both triangles sharing the edge.
+fn edge(a: Pos2, b: Pos2, p: Pos2) -> f32 {
+    if (a.x, a.y) > (b.x, b.y) {
+        return -edge(b, a, p);
+    }
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// pixel center exactly on edge `a b` belongs to the triangle only for these edges.
+/// shared edge runs in opposite direction in the neighbour, so exactly one of them owns it.
+fn top_left(a: Pos2, b: Pos2) -> bool {
+    let d = b - a;
+    d.y > 0.0 || (d.y == 0.0 && d.x < 0.0)
+}
+
+/// premultiplied source over.
+fn blend(dst: Color32, src: [f32; 4]) -> Color32 {
+    let dst = dst.to_array();
+    let inv = 1.0 - src[3] / 255.0;
+    let c = |i: usize| (src[i] + dst[i] as f32 * inv).round().clamp(0.0, 255.0) as u8;
+    Color32::from_rgba_premultiplied(c(0), c(1), c(2), c(3))
+}
+
+fn to_pixmap(image: &ColorImage) -> Pixmap {
+    let mut pixmap = Pixmap::new(image.size[0] as u32, image.size[1] as u32).unwrap();
+    for (dst, src) in pixmap.data_mut().chunks_exact_mut(4).zip(&image.pixels) {
+        dst.copy_from_slice(&src.to_array());
+    }
+    pixmap
+}
+
+#[derive(Clone)]
+struct SkiaState {
+    fill: Color32,
+    stroke: Color32,
+    line_width: f64,
+    clip: Option<ClipMask>,
+}
+
+/// software [`Canvas2d`] following browser semantics.
+pub struct SkiaCanvas<'a> {
+    pixmap: Pixmap,
+    transform: Transform,
+    path: PathBuilder,
+    has_point: bool,
+    state: SkiaState,
+    stack: Vec<SkiaState>,
+    textures: &'a HashMap<TextureId, Pixmap>,
+}
+
+impl<'a> SkiaCanvas<'a> {
+    pub fn new(
+        size: [usize; 2],
+        pixels_per_point: f32,
+        textures: &'a HashMap<TextureId, Pixmap>,
+    ) -> Self {
+        Self {
+            pixmap: Pixmap::new(size[0] as u32, size[1] as u32).unwrap(),
+            transform: Transform::from_scale(pixels_per_point, pixels_per_point),
+            path: PathBuilder::new(),
+            has_point: false,
+            state: SkiaState {
+                fill: Color32::BLACK,
+                stroke: Color32::BLACK,
+                line_width: 1.0,
+                clip: None,
+            },
+            stack: Vec::new(),
+            textures,
+        }
+    }
+    fn paint(color: Color32) -> Paint<'static> {
+        // same as css `#rrggbbaa` the web canvas gets.
+        Paint {
+            shader: Shader::SolidColor(Color::from_rgba8(
+                color.r(),
+                color.g(),
+                color.b(),
+                color.a(),
+            )),
+            anti_alias: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl Canvas2d for SkiaCanvas<'_> {
+    fn begin_path(&mut self) {
+        self.path = PathBuilder::new();
+        self.has_point = false;
+    }
+    fn close_path(&mut self) {
+        self.path.close();
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.path.move_to(x as f32, y as f32);
+        self.has_point = true;
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        if self.has_point {
+            self.path.line_to(x as f32, y as f32);
+        } else {
+            self.move_to(x, y);
+        }
+    }
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let (start, curves) = arc_to_cubics(x, y, radius, start_angle, end_angle);
+        self.line_to(start.0, start.1);
+        for [c1, c2, end] in curves {
+            self.bezier_curve_to(c1.0, c1.1, c2.0, c2.1, end.0, end.1);
+        }
+    }
+    fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        if !self.has_point {
+            self.move_to(cpx, cpy);
+        }
+        self.path
+            .quad_to(cpx as f32, cpy as f32, x as f32, y as f32);
+    }
+    fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        if !self.has_point {
+            self.move_to(cp1x, cp1y);
+        }
+        self.path.cubic_to(
+            cp1x as f32,
+            cp1y as f32,
+            cp2x as f32,
+            cp2y as f32,
+            x as f32,
+            y as f32,
+        );
+    }
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        self.path.push_rect(x as f32, y as f32, w as f32, h as f32);
+        self.has_point = true;
+    }
+    fn set_line_width(&mut self, width: f64) {
+        // canvas ignores zero, negative and non finite value.
+        if width > 0.0 && width.is_finite() {
+            self.state.line_width = width;
+        }
+    }
+    fn set_fill_color(&mut self, color: Color32) {
+        self.state.fill = color;
+    }
+    fn set_stroke_color(&mut self, color: Color32) {
+        self.state.stroke = color;
+    }
+    fn fill(&mut self) {
+        if let Some(path) = self.path.clone().finish() {
+            self.pixmap.fill_path(
+                &path,
+                &Self::paint(self.state.fill),
+                FillRule::Winding,
+                self.transform,
+                self.state.clip.as_ref(),
+            );
+        }
+    }
+    fn stroke(&mut self) {
+        if let Some(path) = self.path.clone().finish() {
+            let stroke = Stroke {
+                width: self.state.line_width as f32,
+                // canvas default. skia uses 4.
+                miter_limit: 10.0,
+                ..Default::default()
+            };
+            self.pixmap.stroke_path(
+                &path,
+                &Self::paint(self.state.stroke),
+                &stroke,
+                self.transform,
+                self.state.clip.as_ref(),
+            );
+        }
+    }
+    fn save(&mut self) {
+        self.stack.push(self.state.clone());
+    }
+    fn restore(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        }
+    }
+    fn clip(&mut self) {
+        let path = match self
+            .path
+            .clone()
+            .finish()
+            .and_then(|path| path.transform(self.transform))
+        {
+            Some(path) => path,
+            None => return,
+        };
+        let (width, height) = (self.pixmap.width(), self.pixmap.height());
+        match &mut self.state.clip {
+            Some(mask) => {
+                mask.intersect_path(&path, FillRule::Winding, true);
+            }
+            None => {
+                let mut mask = ClipMask::new();
+                mask.set_path(width, height, &path, FillRule::Winding, true);
+                self.state.clip = Some(mask);
+            }
+        }
+    }
+    fn draw_texture(&mut self, id: TextureId, source: Rect, dest: Rect) {
+        let texture = match self.textures.get(&id) {
+            Some(texture) => texture,
+            None => return,
+        };
+        let sx = dest.width() / source.width();
+        let sy = dest.height() / source.height();
+        let pattern = Pattern::new(
+            texture.as_ref(),
+            SpreadMode::Pad,
+            // font atlas and textures without options are not smoothed.
+            FilterQuality::Nearest,
+            1.0,
+            Transform::from_row(
+                sx,
+                0.0,
+                0.0,
+                sy,
+                dest.min.x - source.min.x * sx,
+                dest.min.y - source.min.y * sy,
+            ),
+        );
+        let paint = Paint {
+            shader: pattern,
+            ..Default::default()
+        };
+        if let Some(rect) =
+            tiny_skia::Rect::from_xywh(dest.min.x, dest.min.y, dest.width(), dest.height())
+        {
+            self.pixmap
+                .fill_rect(rect, &paint, self.transform, self.state.clip.as_ref());
+        }
+    }
+    fn draw_texture_transformed(
+        &mut self,
+        id: TextureId,
+        source: Rect,
+        transform: [f32; 6],
+        _tint: Color32,
+    ) -> bool {
+        let texture = match self.textures.get(&id) {
+            Some(texture) => texture,
+            None => return true,
+        };
+        let pattern = Pattern::new(
+            texture.as_ref(),
+            SpreadMode::Pad,
+            FilterQuality::Nearest,
+            1.0,
+            Transform::identity(),
+        );
+        let paint = Paint {
+            shader: pattern,
+            ..Default::default()
+        };
+        let [a, b, c, d, e, f] = transform;
+        if let Some(rect) =
+            tiny_skia::Rect::from_xywh(source.min.x, source.min.y, source.width(), source.height())
+        {
+            let transform = self
+                .transform
+                .pre_concat(Transform::from_row(a, b, c, d, e, f));
+            self.pixmap
+                .fill_rect(rect, &paint, transform, self.state.clip.as_ref());
+        }
+        true
+    }
+    fn texture_size(&self, id: TextureId) -> Option<[usize; 2]> {
+        let texture = self.textures.get(&id)?;
+        Some([texture.width() as usize, texture.height() as usize])
+    }
+    fn fill_linear_gradient(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, colors: [Color32; 2]) {
+        let stops = gradient_stops(colors)
+            .iter()
+            .zip([0.0, 1.0])
+            .map(|(c, offset)| {
+                GradientStop::new(offset, Color::from_rgba8(c.r(), c.g(), c.b(), c.a()))
+            })
+            .collect();
+        let shader = LinearGradient::new(
+            Point::from_xy(x0 as f32, y0 as f32),
+            Point::from_xy(x1 as f32, y1 as f32),
+            stops,
+            SpreadMode::Pad,
+            Transform::identity(),
+        );
+        if let (Some(shader), Some(path)) = (shader, self.path.clone().finish()) {
+            let paint = Paint {
+                shader,
+                anti_alias: true,
+                ..Default::default()
+            };
+            self.pixmap.fill_path(
+                &path,
+                &paint,
+                FillRule::Winding,
+                self.transform,
+                self.state.clip.as_ref(),
+            );
+        }
+    }
+    fn set_font(&mut self, _font: &str) {}
+    /// browser fonts can't be reproduced. compare text in [`TextRenderingMode::EGUI`].
+    fn fill_text(&mut self, _text: &str, _x: f64, _y: f64, _max_width: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::{
+        pos2, vec2, CubicBezierShape, Mesh, QuadraticBezierShape, Shape, Stroke as EguiStroke,
+    };
+
+    fn clipped(shape: Shape) -> ClippedShape {
+        ClippedShape(Rect::EVERYTHING, shape)
+    }
+
+    /// panics with every shape out of `tolerance`.
+    fn check(
+        shapes: Vec<Shape>,
+        pixels_per_point: f32,
+        mode: ShapeRenderingMode,
+        tolerance: Tolerance,
+    ) {
+        let size = (64.0 * pixels_per_point) as usize;
+        let mut harness = Harness::new([size, size], pixels_per_point);
+        harness.shape_rendering_mode = mode;
+        let shapes: Vec<_> = shapes.into_iter().map(clipped).collect();
+        let failures: Vec<_> = harness
+            .compare(&shapes, &tolerance)
+            .into_iter()
+            .filter(|diff| !diff.within(&tolerance))
+            .map(|diff| format!("{:?}\n  {:?}", shapes[diff.index].1, diff))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn report_identical_shape() {
+        let harness = Harness::new([16, 16], 1.0);
+        let shape = clipped(Shape::rect_filled(
+            Rect::from_min_size(pos2(4.0, 4.0), vec2(8.0, 8.0)),
+            0.0,
+            Color32::RED,
+        ));
+        let diff = &harness.compare(
+            &[shape],
+            &Tolerance {
+                channel: 0,
+                pixels: 0,
+            },
+        )[0];
+        assert_eq!(diff.differing_pixels, 0);
+    }
+
+    fn rect() -> Rect {
+        Rect::from_min_size(pos2(8.0, 8.0), vec2(40.0, 30.0))
+    }
+
+    /// `pixels` allowed past the channel difference anti aliasing accounts for.
+    fn pixels(pixels: usize) -> Tolerance {
+        Tolerance {
+            channel: 32,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn fills_match() {
+        let shapes = vec![
+            Shape::rect_filled(rect(), 0.0, Color32::RED),
+            Shape::rect_filled(rect(), 6.0, Color32::RED),
+            Shape::circle_filled(pos2(30.0, 30.0), 12.0, Color32::GREEN),
+            Shape::convex_polygon(
+                vec![pos2(4.0, 4.0), pos2(50.0, 10.0), pos2(20.0, 50.0)],
+                Color32::BLUE,
+                EguiStroke::none(),
+            ),
+        ];
+        check(shapes.clone(), 1.0, ShapeRenderingMode::Canvas, pixels(0));
+        // tiny-skia samples 4 sub scanlines per pixel, steep curved edges are off the most.
+        check(shapes, 2.0, ShapeRenderingMode::Canvas, pixels(6));
+    }
+
+    #[test]
+    fn strokes_match() {
+        let stroke = EguiStroke::new(2.0, Color32::WHITE);
+        let shape = Shape::rect_stroke(rect(), 6.0, stroke);
+        check(vec![shape], 1.0, ShapeRenderingMode::Canvas, pixels(0));
+        let shape =
+            Shape::circle_stroke(pos2(30.0, 30.0), 12.0, EguiStroke::new(3.0, Color32::GREEN));
+        // same sub scanlines as in fills.
+        check(vec![shape], 1.0, ShapeRenderingMode::Canvas, pixels(25));
+        // ends of open lines are feathered by egui, butt by canvas.
+        let shapes = vec![
+            Shape::line_segment([pos2(4.0, 4.0), pos2(50.0, 40.0)], stroke),
+            Shape::line(
+                vec![pos2(4.0, 4.0), pos2(50.0, 10.0), pos2(20.0, 50.0)],
+                stroke,
+            ),
+        ];
+        check(shapes, 1.0, ShapeRenderingMode::Canvas, pixels(7));
+    }
+
+    #[test]
+    fn sharp_rect_stroke_matches() {
+        // canvas corners are mitered, egui ones are not.
+        let shape = Shape::rect_stroke(rect(), 0.0, EguiStroke::new(3.0, Color32::WHITE));
+        check(vec![shape], 1.0, ShapeRenderingMode::Canvas, pixels(8));
+        // strokes up to a pixel are hairlines in skia, as in chrome. they have no joins.
+        let shape = Shape::rect_stroke(rect(), 0.0, EguiStroke::new(1.0, Color32::WHITE));
+        check(vec![shape], 1.0, ShapeRenderingMode::Canvas, pixels(8));
+    }
+
+    #[test]
+    fn beziers_match() {
+        let beziers = |width| {
+            let stroke = EguiStroke::new(width, Color32::WHITE);
+            vec![
+                Shape::QuadraticBezier(QuadraticBezierShape::from_points_stroke(
+                    [pos2(4.0, 50.0), pos2(30.0, 0.0), pos2(56.0, 50.0)],
+                    true,
+                    Color32::BLUE,
+                    stroke,
+                )),
+                Shape::CubicBezier(CubicBezierShape::from_points_stroke(
+                    [
+                        pos2(4.0, 50.0),
+                        pos2(20.0, 0.0),
+                        pos2(40.0, 60.0),
+                        pos2(56.0, 10.0),
+                    ],
+                    false,
+                    Color32::TRANSPARENT,
+                    stroke,
+                )),
+            ]
+        };
+        // sub scanlines step coverage of flat parts, and open ends are butt.
+        check(beziers(2.0), 1.0, ShapeRenderingMode::Canvas, pixels(48));
+        // hairline curves are split into fewer lines than egui flattens them to.
+        check(beziers(1.0), 1.0, ShapeRenderingMode::Canvas, pixels(70));
+    }
+
+    #[test]
+    fn images_match() {
+        let mut harness = Harness::new([64, 64], 1.0);
+        let id = TextureId::User(1);
+        let pixels = (0..16 * 16)
+            .map(|i| {
+                if (i / 16 + i) % 2 == 0 {
+                    Color32::WHITE
+                } else {
+                    Color32::BLUE
+                }
+            })
+            .collect();
+        harness.set_texture(
+            id,
+            ColorImage {
+                size: [16, 16],
+                pixels,
+            },
+        );
+        let mut mesh = Mesh::with_texture(id);
+        mesh.add_rect_with_uv(
+            Rect::from_min_size(pos2(0.0, 0.0), vec2(32.0, 32.0)),
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        let shape = Shape::mesh(mesh);
+        let tolerance = Tolerance {
+            channel: 32,
+            pixels: 0,
+        };
+        let diffs = harness.compare(&[clipped(shape)], &tolerance);
+        assert!(diffs[0].within(&tolerance), "{:?}", diffs[0]);
+    }
+
+    #[test]
+    fn transformed_images_match() {
+        let mut harness = Harness::new([64, 64], 1.0);
+        let id = TextureId::User(1);
+        let pixels = (0..16 * 16)
+            .map(|i| match (i % 16 < 8, i / 16 < 4) {
+                (true, true) => Color32::RED,
+                (true, false) => Color32::WHITE,
+                (false, _) => Color32::BLUE,
+            })
+            .collect();
+        harness.set_texture(
+            id,
+            ColorImage {
+                size: [16, 16],
+                pixels,
+            },
+        );
+        let quad = |uv: [Pos2; 4]| {
+            let mut mesh = Mesh::with_texture(id);
+            let corners = [
+                pos2(8.0, 8.0),
+                pos2(40.0, 8.0),
+                pos2(40.0, 40.0),
+                pos2(8.0, 40.0),
+            ];
+            for (pos, uv) in corners.into_iter().zip(uv) {
+                mesh.colored_vertex(pos, Color32::WHITE);
+                mesh.vertices.last_mut().unwrap().uv = uv;
+            }
+            mesh.add_triangle(0, 1, 2);
+            mesh.add_triangle(0, 2, 3);
+            clipped(Shape::mesh(mesh))
+        };
+        let [nw, ne, se, sw] = [
+            pos2(0.0, 0.0),
+            pos2(1.0, 0.0),
+            pos2(1.0, 1.0),
+            pos2(0.0, 1.0),
+        ];
+        let shapes = [
+            // mirrored
+            quad([ne, nw, sw, se]),
+            // quarter turn
+            quad([sw, nw, ne, se]),
+        ];
+        let tolerance = Tolerance {
+            channel: 32,
+            pixels: 0,
+        };
+        for diff in harness.compare(&shapes, &tolerance) {
+            assert!(diff.within(&tolerance), "{:?}", diff);
+        }
+    }
+
+    #[test]
+    fn tessellated_mode_matches() {
+        let stroke = EguiStroke::new(1.0, Color32::WHITE);
+        let shapes = vec![
+            Shape::rect_filled(rect(), 6.0, Color32::RED),
+            Shape::rect_stroke(rect(), 0.0, stroke),
+            Shape::circle_stroke(pos2(30.0, 30.0), 12.0, EguiStroke::new(3.0, Color32::GREEN)),
+            Shape::line(
+                vec![pos2(4.0, 4.0), pos2(50.0, 10.0), pos2(20.0, 50.0)],
+                stroke,
+            ),
+            Shape::QuadraticBezier(QuadraticBezierShape::from_points_stroke(
+                [pos2(4.0, 50.0), pos2(30.0, 0.0), pos2(56.0, 50.0)],
+                true,
+                Color32::BLUE,
+                stroke,
+            )),
+        ];
+        // canvas anti aliases each run, so there are faint seams where runs meet.
+        check(
+            shapes,
+            1.0,
+            ShapeRenderingMode::Tessellated,
+            Tolerance {
+                channel: 64,
+                pixels: 24,
+            },
+        );
+    }
+}
@@ -0,0 +1,565 @@
+//! pdf export of a frame.
+//!
+//! same translation as [`Renderer`](crate::Renderer), written as one pdf page.
+//! * path, arc and bezier -> path operators. arcs become cubic beziers.
+//! * clip rectangle -> `W n`
+//! * texture -> image XObject with soft mask
+//! * text -> glyph images ([`TextRenderingMode::EGUI`]) or Helvetica text ([`TextRenderingMode::Browser`]).
+//!   Helvetica covers latin-1 only, use EGUI mode for other scripts.
+use crate::canvas::{arc_to_cubics, paint_clipped, Canvas2d};
+use crate::cpu_texture::CpuTextures;
+use crate::TextRenderingMode;
+use epaint::{textures::TexturesDelta, ClippedShape, Color32, ImageDelta, Rect, TextureId, Vec2};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// keeps textures and writes frames as single page pdf documents.
+///
+/// feed `textures_delta` of every frame by [`PdfExporter::update_textures`]
+/// so that font and user textures are available when exporting.
+pub struct PdfExporter {
+    textures: CpuTextures,
+    rendering_mode: TextRenderingMode,
+}
+
+impl Default for PdfExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfExporter {
+    pub fn new() -> Self {
+        Self {
+            textures: CpuTextures::default(),
+            rendering_mode: TextRenderingMode::EGUI,
+        }
+    }
+    /// set text rendering mode
+    pub fn set_rendering_mode(&mut self, render_mode: TextRenderingMode) {
+        self.rendering_mode = render_mode;
+    }
+    /// apply texture delta of one frame.
+    ///
+    /// freed textures are kept until next call, so shapes of the same frame can still use them.
+    pub fn update_textures(&mut self, textures_delta: &TexturesDelta) {
+        self.textures.update(textures_delta);
+    }
+    pub fn set_texture(&mut self, id: TextureId, image_delta: &ImageDelta) {
+        self.textures.set(id, image_delta);
+    }
+    pub fn free_texture(&mut self, id: TextureId) {
+        self.textures.free(id);
+    }
+    /// write shapes as pdf document.
+    ///
+    /// `screen_size` is the size of ui in points.
+    /// `page_size` is in pdf units (1/72 inch). A4 portrait is 595 x 842.
+    /// ui is scaled to fit the page keeping aspect ratio and centered.
+    pub fn export(&self, shapes: &[ClippedShape], screen_size: Vec2, page_size: Vec2) -> Vec<u8> {
+        let scale = (page_size.x / screen_size.x).min(page_size.y / screen_size.y) as f64;
+        let margin_x = (page_size.x as f64 - screen_size.x as f64 * scale) / 2.0;
+        let margin_y = (page_size.y as f64 - screen_size.y as f64 * scale) / 2.0;
+        let mut canvas = PdfCanvas::new(&self.textures);
+        // flip y axis. ui coordinates are y down.
+        writeln!(
+            canvas.content,
+            "q {} 0 0 {} {} {} cm",
+            num(scale),
+            num(-scale),
+            num(margin_x),
+            num(page_size.y as f64 - margin_y)
+        )
+        .unwrap();
+        for shape in shapes {
+            paint_clipped(&mut canvas, self.rendering_mode, shape);
+        }
+        canvas.finish(page_size)
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    fill: Color32,
+    stroke: Color32,
+    line_width: f64,
+    font_size: f64,
+}
+
+struct PdfCanvas<'a> {
+    textures: &'a CpuTextures,
+    /// texture and its XObject number.
+    images: BTreeMap<TextureId, usize>,
+    fill_alphas: BTreeSet<u8>,
+    stroke_alphas: BTreeSet<u8>,
+    uses_font: bool,
+    content: String,
+    path: String,
+    current: (f64, f64),
+    has_point: bool,
+    state: State,
+    stack: Vec<State>,
+}
+
+impl<'a> PdfCanvas<'a> {
+    fn new(textures: &'a CpuTextures) -> Self {
+        Self {
+            textures,
+            images: BTreeMap::new(),
+            fill_alphas: BTreeSet::new(),
+            stroke_alphas: BTreeSet::new(),
+            uses_font: false,
+            content: String::new(),
+            path: String::new(),
+            current: (0.0, 0.0),
+            has_point: false,
+            state: State {
+                fill: Color32::BLACK,
+                stroke: Color32::BLACK,
+                line_width: 1.0,
+                font_size: 10.0,
+            },
+            stack: vec![],
+        }
+    }
+    fn point(&mut self, x: f64, y: f64) {
+        let op = if self.has_point { 'l' } else { 'm' };
+        writeln!(self.path, "{} {} {}", num(x), num(y), op).unwrap();
+        self.current = (x, y);
+        self.has_point = true;
+    }
+    fn curve(&mut self, c1: (f64, f64), c2: (f64, f64), end: (f64, f64)) {
+        writeln!(
+            self.path,
+            "{} {} {} {} {} {} c",
+            num(c1.0),
+            num(c1.1),
+            num(c2.0),
+            num(c2.1),
+            num(end.0),
+            num(end.1)
+        )
+        .unwrap();
+        self.current = end;
+    }
+    /// set color and alpha of `rg`/`RG`.
+    fn color(&mut self, color: Color32, stroking: bool) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        let op = if stroking { "RG" } else { "rg" };
+        writeln!(
+            self.content,
+            "{} {} {} {}",
+            num(r as f64 / 255.0),
+            num(g as f64 / 255.0),
+            num(b as f64 / 255.0),
+            op
+        )
+        .unwrap();
+        if stroking {
+            self.stroke_alphas.insert(a);
+            writeln!(self.content, "/S{} gs", a).unwrap();
+        } else {
+            self.fill_alphas.insert(a);
+            writeln!(self.content, "/F{} gs", a).unwrap();
+        }
+    }
+    fn finish(mut self, page_size: Vec2) -> Vec<u8> {
+        while !self.stack.is_empty() {
+            self.restore();
+        }
+        self.content.push_str("Q\n");
+
+        // object numbers go by `n`, not by texture id.
+        let mut images: Vec<_> = self.images.iter().collect();
+        images.sort_by_key(|(_, n)| **n);
+
+        let mut objects: Vec<Vec<u8>> = Vec::new();
+        // 1 catalog, 2 pages, 3 page, 4 content, 5 font. images follow.
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        objects.push(b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_vec());
+
+        let mut resources = String::from("<< ");
+        if self.uses_font {
+            resources.push_str("/Font << /F1 5 0 R >> ");
+        }
+        resources.push_str("/ExtGState << ");
+        for a in &self.fill_alphas {
+            write!(resources, "/F{} << /ca {} >> ", a, num(*a as f64 / 255.0)).unwrap();
+        }
+        for a in &self.stroke_alphas {
+            write!(resources, "/S{} << /CA {} >> ", a, num(*a as f64 / 255.0)).unwrap();
+        }
+        resources.push_str(">> /XObject << ");
+        for (_, &n) in &images {
+            write!(resources, "/Im{} {} 0 R ", n, 6 + n * 2).unwrap();
+        }
+        resources.push_str(">> >>");
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources {} /Contents 4 0 R >>",
+                num(page_size.x as f64),
+                num(page_size.y as f64),
+                resources
+            )
+            .into_bytes(),
+        );
+        objects.push(stream("", self.content.as_bytes()));
+        objects.push(
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_vec(),
+        );
+        for (id, n) in images {
+            let image = self.textures.get(id).unwrap();
+            let mut rgb = Vec::with_capacity(image.pixels.len() * 3);
+            let mut alpha = Vec::with_capacity(image.pixels.len());
+            for pixel in &image.pixels {
+                let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+                rgb.extend_from_slice(&[r, g, b]);
+                alpha.push(a);
+            }
+            let [w, h] = image.size;
+            objects.push(stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /SMask {} 0 R",
+                    w,
+                    h,
+                    7 + n * 2
+                ),
+                &rgb,
+            ));
+            objects.push(stream(
+                &format!(
+                    "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8",
+                    w, h
+                ),
+                &alpha,
+            ));
+        }
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            writeln!(out, "{} 0 obj", i + 1).unwrap();
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).unwrap();
+        for offset in offsets {
+            writeln!(out, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(
+            out,
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .unwrap();
+        out
+    }
+}
+
+impl Canvas2d for PdfCanvas<'_> {
+    fn begin_path(&mut self) {
+        self.path.clear();
+        self.has_point = false;
+    }
+    fn close_path(&mut self) {
+        self.path.push_str("h\n");
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        writeln!(self.path, "{} {} m", num(x), num(y)).unwrap();
+        self.current = (x, y);
+        self.has_point = true;
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.point(x, y);
+    }
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let (start, curves) = arc_to_cubics(x, y, radius, start_angle, end_angle);
+        self.point(start.0, start.1);
+        for [c1, c2, end] in curves {
+            self.curve(c1, c2, end);
+        }
+    }
+    fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        // pdf has cubic bezier only.
+        let (x0, y0) = self.current;
+        let c1 = (x0 + 2.0 / 3.0 * (cpx - x0), y0 + 2.0 / 3.0 * (cpy - y0));
+        let c2 = (x + 2.0 / 3.0 * (cpx - x), y + 2.0 / 3.0 * (cpy - y));
+        self.curve(c1, c2, (x, y));
+    }
+    fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        self.curve((cp1x, cp1y), (cp2x, cp2y), (x, y));
+    }
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        writeln!(self.path, "{} {} {} {} re", num(x), num(y), num(w), num(h)).unwrap();
+        self.current = (x, y);
+        self.has_point = true;
+    }
+    fn set_line_width(&mut self, width: f64) {
+        // canvas ignores zero, negative and non finite value.
+        if width > 0.0 && width.is_finite() {
+            self.state.line_width = width;
+        }
+    }
+    fn set_fill_color(&mut self, color: Color32) {
+        self.state.fill = color;
+    }
+    fn set_stroke_color(&mut self, color: Color32) {
+        self.state.stroke = color;
+    }
+    fn fill(&mut self) {
+        if self.path.is_empty() || self.state.fill.a() == 0 {
+            return;
+        }
+        self.color(self.state.fill, false);
+        self.content.push_str(&self.path);
+        self.content.push_str("f\n");
+    }
+    fn stroke(&mut self) {
+        if self.path.is_empty() || self.state.stroke.a() == 0 {
+            return;
+        }
+        self.color(self.state.stroke, true);
+        writeln!(self.content, "{} w", num(self.state.line_width)).unwrap();
+        self.content.push_str(&self.path);
+        self.content.push_str("S\n");
+    }
+    fn save(&mut self) {
+        self.stack.push(self.state.clone());
+        self.content.push_str("q\n");
+    }
+    fn restore(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+            self.content.push_str("Q\n");
+        }
+    }
+    fn clip(&mut self) {
+        self.content.push_str(&self.path);
+        self.content.push_str("W n\n");
+    }
+    fn draw_texture(&mut self, id: TextureId, source: Rect, dest: Rect) {
+        let image = match self.textures.get(&id) {
+            Some(image) => image,
+            None => return,
+        };
+        if source.width() <= 0.0 || source.height() <= 0.0 {
+            return;
+        }
+        let next = self.images.len();
+        let n = *self.images.entry(id).or_insert(next);
+        // scale whole image so that source rectangle lands on dest.
+        let sx = dest.width() as f64 / source.width() as f64;
+        let sy = dest.height() as f64 / source.height() as f64;
+        let w = image.size[0] as f64 * sx;
+        let h = image.size[1] as f64 * sy;
+        let x = dest.min.x as f64 - source.min.x as f64 * sx;
+        let y = dest.min.y as f64 - source.min.y as f64 * sy;
+        writeln!(
+            self.content,
+            "q {} {} {} {} re W n {} 0 0 {} {} {} cm /Im{} Do Q",
+            num(dest.min.x as f64),
+            num(dest.min.y as f64),
+            num(dest.width() as f64),
+            num(dest.height() as f64),
+            num(w),
+            num(-h),
+            num(x),
+            num(y + h),
+            n
+        )
+        .unwrap();
+    }
+    fn set_font(&mut self, font: &str) {
+        // "{size}px family"
+        if let Some(size) = font
+            .split_whitespace()
+            .find_map(|part| part.strip_suffix("px")?.parse::<f64>().ok())
+        {
+            self.state.font_size = size;
+        }
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, _max_width: f64) {
+        if self.state.fill.a() == 0 {
+            return;
+        }
+        self.uses_font = true;
+        self.color(self.state.fill, false);
+        // text matrix flips back y axis, otherwise glyphs are upside down.
+        writeln!(
+            self.content,
+            "BT /F1 {} Tf 1 0 0 -1 {} {} Tm ({}) Tj ET",
+            num(self.state.font_size),
+            num(x),
+            num(y),
+            pdf_string(text)
+        )
+        .unwrap();
+    }
+    fn texture_size(&self, id: TextureId) -> Option<[usize; 2]> {
+        self.textures.get(&id).map(|image| image.size)
+    }
+}
+
+/// stream object compressed by deflate.
+fn stream(dict: &str, data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut out = format!(
+        "<< {} /Length {} /Filter /FlateDecode >>\nstream\n",
+        dict,
+        compressed.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(&compressed);
+    out.extend_from_slice(b"\nendstream");
+    out
+}
+
+/// latin-1 string literal. other characters become `?`.
+fn pdf_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            c if (c as u32) < 0x100 => write!(out, "\\{:03o}", c as u32).unwrap(),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+/// short number. 1/1000 is enough.
+fn num(v: f64) -> String {
+    let v = (v * 1000.0).round() / 1000.0;
+    if v == 0.0 {
+        // avoid "-0"
+        "0".to_owned()
+    } else {
+        format!("{}", v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::{pos2, vec2, AlphaImage, Shape, Stroke};
+
+    #[test]
+    fn single_page_document() {
+        let mut exporter = PdfExporter::new();
+        let mut textures_delta = TexturesDelta::default();
+        textures_delta.set.insert(
+            TextureId::Managed(0),
+            ImageDelta::full(AlphaImage {
+                size: [4, 4],
+                pixels: vec![255; 16],
+            }),
+        );
+        exporter.update_textures(&textures_delta);
+        let clip = Rect::from_min_max(pos2(0.0, 0.0), pos2(100.0, 100.0));
+        let shapes = vec![
+            ClippedShape(
+                clip,
+                Shape::circle_stroke(pos2(50.0, 50.0), 10.0, Stroke::new(1.0, Color32::RED)),
+            ),
+            ClippedShape(
+                clip,
+                Shape::rect_filled(
+                    Rect::from_min_max(pos2(5.0, 5.0), pos2(20.0, 20.0)),
+                    3.0,
+                    Color32::BLUE,
+                ),
+            ),
+        ];
+        let pdf = exporter.export(&shapes, vec2(100.0, 100.0), vec2(595.0, 842.0));
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.contains("/MediaBox [0 0 595 842]"));
+        // textures not drawn are not embedded.
+        assert!(!text.contains("/Subtype /Image"));
+        // xref points at each object.
+        let xref = pdf.windows(5).rposition(|w| w == b"xref\n").unwrap();
+        let table = String::from_utf8(pdf[xref..].to_vec()).unwrap();
+        for (i, line) in table.lines().skip(3).take(5).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn glyph_draw_embeds_image() {
+        let mut textures = CpuTextures::default();
+        textures.set(
+            TextureId::Managed(0),
+            &ImageDelta::full(AlphaImage {
+                size: [4, 4],
+                pixels: vec![255; 16],
+            }),
+        );
+        let mut canvas = PdfCanvas::new(&textures);
+        for i in 0..2 {
+            canvas.draw_texture(
+                TextureId::Managed(0),
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(2.0, 2.0)),
+                Rect::from_min_size(pos2(i as f32 * 10.0, 0.0), vec2(2.0, 2.0)),
+            );
+        }
+        assert_eq!(canvas.content.matches("/Im0 Do").count(), 2);
+        let pdf = canvas.finish(vec2(100.0, 100.0));
+        let text = String::from_utf8_lossy(&pdf);
+        assert_eq!(text.matches("/Subtype /Image").count(), 2);
+    }
+
+    #[test]
+    fn images_follow_draw_order() {
+        let mut textures = CpuTextures::default();
+        for (id, width) in [(TextureId::Managed(0), 4), (TextureId::User(1), 8)] {
+            textures.set(
+                id,
+                &ImageDelta::full(AlphaImage {
+                    size: [width, 2],
+                    pixels: vec![255; width * 2],
+                }),
+            );
+        }
+        let mut canvas = PdfCanvas::new(&textures);
+        // user texture first, so its `n` is smaller though its id sorts last.
+        for id in [TextureId::User(1), TextureId::Managed(0)] {
+            canvas.draw_texture(
+                id,
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(2.0, 2.0)),
+                Rect::from_min_size(pos2(0.0, 0.0), vec2(2.0, 2.0)),
+            );
+        }
+        let pdf = canvas.finish(vec2(100.0, 100.0));
+        let text = String::from_utf8_lossy(&pdf);
+        let object = |n: usize| {
+            let start = text.find(&format!("\n{} 0 obj\n", n)).unwrap();
+            text[start..].split("endobj").next().unwrap().to_owned()
+        };
+        assert!(text.contains("/Im0 6 0 R /Im1 8 0 R"));
+        assert!(object(6).contains("/Width 8 /Height 2 /ColorSpace /DeviceRGB"));
+        assert!(object(6).contains("/SMask 7 0 R"));
+        assert!(object(7).contains("/Width 8 /Height 2 /ColorSpace /DeviceGray"));
+        assert!(object(8).contains("/Width 4 /Height 2 /ColorSpace /DeviceRGB"));
+        assert!(object(8).contains("/SMask 9 0 R"));
+        assert!(object(9).contains("/Width 4 /Height 2 /ColorSpace /DeviceGray"));
+    }
+
+    #[test]
+    fn string_escape() {
+        assert_eq!(pdf_string("a(b)\\"), "a\\(b\\)\\\\");
+        assert_eq!(pdf_string("é日"), "\\351?");
+    }
+}
@@ -0,0 +1,178 @@
+use super::protocol::{encode_input, modifiers, FrameDecoder, InputEvent};
+use crate::Renderer;
+use epaint::Color32;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{BinaryType, KeyboardEvent, MessageEvent, MouseEvent, WebSocket, WheelEvent};
+
+/// thin client. paint frames received from native app and send input back.
+pub struct RemoteClient {
+    renderer: Renderer,
+    decoder: FrameDecoder,
+    socket: WebSocket,
+    clear_color: Color32,
+}
+
+impl RemoteClient {
+    /// connect to `url` and start painting into `renderer`.
+    ///
+    /// input listeners are attached to the canvas of `renderer`.
+    pub fn connect(url: &str, renderer: Renderer) -> Option<Rc<RefCell<Self>>> {
+        let socket = WebSocket::new(url).ok()?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let canvas = renderer.visible_canvas()?;
+        canvas.set_tab_index(0);
+        let client = Rc::new(RefCell::new(Self {
+            renderer,
+            decoder: FrameDecoder::new(),
+            socket: socket.clone(),
+            clear_color: Color32::BLACK,
+        }));
+
+        {
+            let client = client.clone();
+            let onmessage = Closure::wrap(Box::new(move |event: MessageEvent| {
+                if let Ok(buffer) = event.data().dyn_into::<js_sys::ArrayBuffer>() {
+                    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+                    client.borrow_mut().on_message(&bytes);
+                }
+            }) as Box<dyn FnMut(MessageEvent)>);
+            socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+            onmessage.forget();
+        }
+        {
+            let client = client.clone();
+            let onopen = Closure::wrap(Box::new(move || {
+                client.borrow().send_resize();
+            }) as Box<dyn FnMut()>);
+            socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+            onopen.forget();
+        }
+
+        let add_mouse_listener = |name: &str, map: fn(&MouseEvent) -> InputEvent| {
+            let client = client.clone();
+            let listener = Closure::wrap(Box::new(move |event: MouseEvent| {
+                client.borrow().send(&[map(&event)]);
+            }) as Box<dyn FnMut(MouseEvent)>);
+            canvas
+                .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                .unwrap();
+            listener.forget();
+        };
+        add_mouse_listener("mousemove", |event| InputEvent::PointerMoved {
+            x: event.offset_x() as f32,
+            y: event.offset_y() as f32,
+        });
+        add_mouse_listener("mousedown", |event| pointer_button(event, true));
+        add_mouse_listener("mouseup", |event| pointer_button(event, false));
+        add_mouse_listener("mouseleave", |_| InputEvent::PointerGone);
+        {
+            let client = client.clone();
+            let listener = Closure::wrap(Box::new(move |event: WheelEvent| {
+                event.prevent_default();
+                client.borrow().send(&[InputEvent::Scroll {
+                    dx: -event.delta_x() as f32,
+                    dy: -event.delta_y() as f32,
+                }]);
+            }) as Box<dyn FnMut(WheelEvent)>);
+            canvas
+                .add_event_listener_with_callback("wheel", listener.as_ref().unchecked_ref())
+                .unwrap();
+            listener.forget();
+        }
+        for (name, pressed) in [("keydown", true), ("keyup", false)] {
+            let client = client.clone();
+            let listener = Closure::wrap(Box::new(move |event: KeyboardEvent| {
+                let key = event.key();
+                let mut events = vec![InputEvent::Key {
+                    key: key.clone(),
+                    pressed,
+                    modifiers: key_modifiers(&event),
+                }];
+                // printable character. browser reports it as single char key.
+                if pressed && key.chars().count() == 1 && !event.ctrl_key() && !event.meta_key() {
+                    events.push(InputEvent::Text(key));
+                }
+                event.prevent_default();
+                client.borrow().send(&events);
+            }) as Box<dyn FnMut(KeyboardEvent)>);
+            canvas
+                .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                .unwrap();
+            listener.forget();
+        }
+        for (name, focused) in [("focus", true), ("blur", false)] {
+            let client = client.clone();
+            let listener = Closure::wrap(Box::new(move || {
+                client.borrow().send(&[InputEvent::FocusChanged(focused)]);
+            }) as Box<dyn FnMut()>);
+            canvas
+                .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                .unwrap();
+            listener.forget();
+        }
+        Some(client)
+    }
+    /// color used to clear canvas before each frame.
+    pub fn set_clear_color(&mut self, color: Color32) {
+        self.clear_color = color;
+    }
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+    /// send canvas size. call this after canvas is resized.
+    pub fn send_resize(&self) {
+        if let Some(canvas) = self.renderer.visible_canvas() {
+            self.send(&[InputEvent::Resize {
+                width: canvas.client_width() as f32,
+                height: canvas.client_height() as f32,
+                // render scale may be lowered, server lays out at device ratio.
+                pixels_per_point: crate::window_dpr() as f32,
+            }]);
+        }
+    }
+    pub fn send(&self, events: &[InputEvent]) {
+        if self.socket.ready_state() == WebSocket::OPEN {
+            let message = encode_input(events);
+            if let Err(err) = self.socket.send_with_u8_array(&message) {
+                log::warn!("failed to send input {:?}", err);
+            }
+        }
+    }
+    fn on_message(&mut self, bytes: &[u8]) {
+        match self.decoder.decode(bytes) {
+            Some(frame) => {
+                self.renderer.clear(&self.clear_color);
+                self.renderer
+                    .paint_and_update_texture(&frame.shapes, frame.textures_delta);
+            }
+            None => log::warn!("malformed frame ({} bytes)", bytes.len()),
+        }
+    }
+}
+
+fn pointer_button(event: &MouseEvent, pressed: bool) -> InputEvent {
+    InputEvent::PointerButton {
+        x: event.offset_x() as f32,
+        y: event.offset_y() as f32,
+        button: event.button() as u8,
+        pressed,
+        modifiers: mouse_modifiers(event),
+    }
+}
+
+fn mouse_modifiers(event: &MouseEvent) -> u8 {
+    (event.alt_key() as u8 * modifiers::ALT)
+        | (event.ctrl_key() as u8 * modifiers::CTRL)
+        | (event.shift_key() as u8 * modifiers::SHIFT)
+        | (event.meta_key() as u8 * modifiers::META)
+}
+
+fn key_modifiers(event: &KeyboardEvent) -> u8 {
+    (event.alt_key() as u8 * modifiers::ALT)
+        | (event.ctrl_key() as u8 * modifiers::CTRL)
+        | (event.shift_key() as u8 * modifiers::SHIFT)
+        | (event.meta_key() as u8 * modifiers::META)
+}
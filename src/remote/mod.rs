@@ -0,0 +1,102 @@
+//! remote rendering.
+//!
+//! native egui app encodes each frame by [`FrameEncoder`] and sends it as binary WebSocket message.
+//! browser page runs [`RemoteClient`], paints frames by [`crate::Renderer`] and sends input back.
+//!
+//! transport is up to native app. any WebSocket server works.
+//!
+//! ```ignore
+//! // native side
+//! let mut encoder = FrameEncoder::new();
+//! let FullOutput { shapes, textures_delta, .. } = ctx.end_frame();
+//! socket.send_binary(encoder.encode(&shapes, &textures_delta));
+//! // on message from client
+//! for event in decode_input(&message).unwrap_or_default() { /* feed into egui::RawInput */ }
+//! ```
+#[cfg(target_arch = "wasm32")]
+mod client;
+mod protocol;
+
+#[cfg(target_arch = "wasm32")]
+pub use client::RemoteClient;
+pub use protocol::{
+    decode_input, encode_input, modifiers, Frame, FrameDecoder, FrameEncoder, InputEvent,
+    PROTOCOL_VERSION,
+};
+
+/// loopback harness.
+///
+/// native app and client run on one machine and talk over TCP on localhost.
+/// each message is prefixed by u32 length instead of WebSocket framing.
+#[cfg(test)]
+mod loopback {
+    use super::*;
+    use epaint::{pos2, textures::TexturesDelta, ClippedShape, Color32, Rect, Shape};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn write_message(stream: &mut TcpStream, message: &[u8]) {
+        stream
+            .write_all(&(message.len() as u32).to_le_bytes())
+            .unwrap();
+        stream.write_all(message).unwrap();
+    }
+
+    fn read_message(stream: &mut TcpStream) -> Vec<u8> {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut message = vec![0; u32::from_le_bytes(len) as usize];
+        stream.read_exact(&mut message).unwrap();
+        message
+    }
+
+    fn frame(t: f32) -> Vec<ClippedShape> {
+        let clip = Rect::from_min_max(pos2(0.0, 0.0), pos2(200.0, 200.0));
+        let mut shapes: Vec<ClippedShape> = (0..50)
+            .map(|i| {
+                ClippedShape(
+                    clip,
+                    Shape::circle_filled(pos2(i as f32, 10.0), 2.0, Color32::WHITE),
+                )
+            })
+            .collect();
+        shapes.push(ClippedShape(
+            clip,
+            Shape::circle_filled(pos2(t, t), 4.0, Color32::RED),
+        ));
+        shapes
+    }
+
+    #[test]
+    fn native_app_to_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // native app. sends 3 frames, then waits for input.
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut encoder = FrameEncoder::new();
+            let mut sizes = vec![];
+            for t in 0..3 {
+                let message = encoder.encode(&frame(t as f32), &TexturesDelta::default());
+                sizes.push(message.len());
+                write_message(&mut stream, &message);
+            }
+            let events = decode_input(&read_message(&mut stream)).unwrap();
+            (sizes, events)
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut decoder = FrameDecoder::new();
+        for t in 0..3 {
+            let decoded = decoder.decode(&read_message(&mut stream)).unwrap();
+            assert_eq!(decoded.shapes, frame(t as f32));
+        }
+        let input = vec![InputEvent::PointerMoved { x: 3.0, y: 4.0 }];
+        write_message(&mut stream, &encode_input(&input));
+
+        let (sizes, events) = server.join().unwrap();
+        assert_eq!(events, input);
+        // only moving circle is resent.
+        assert!(sizes[1] * 10 < sizes[0]);
+    }
+}
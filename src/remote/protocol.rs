@@ -0,0 +1,1179 @@
+//! wire format of remote rendering.
+//!
+//! every message is one binary WebSocket message.
+//! all numbers are little endian.
+//!
+//! server -> client
+//! * `MSG_FRAME` : texture delta + shape operations.
+//!
+//! client -> server
+//! * `MSG_INPUT` : input events collected since last message.
+use epaint::{
+    emath::Align,
+    mutex::Arc,
+    text::{Glyph, LayoutJob, LayoutSection, Row, RowVisuals, TextFormat},
+    textures::TexturesDelta,
+    AlphaImage, CircleShape, ClippedShape, Color32, ColorImage, CubicBezierShape, FontFamily,
+    FontId, Galley, ImageData, ImageDelta, Mesh, PathShape, Pos2, QuadraticBezierShape, Rect,
+    RectShape, Rounding, Shape, Stroke, TextShape, TextureId, Vertex,
+};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// bumped when wire format changes.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+const MSG_FRAME: u8 = 0;
+const MSG_INPUT: u8 = 1;
+
+const OP_COPY: u8 = 0;
+const OP_NEW: u8 = 1;
+
+/// deepest `Shape::Vec` nesting decoded. deeper messages are malformed, not a stack overflow.
+const MAX_DEPTH: usize = 32;
+
+/// most shapes of a decoded frame, nested ones included. copies repeat shapes of previous
+/// frame, so without a limit each frame could grow from the last.
+const MAX_SHAPES: usize = 1 << 20;
+
+/// modifier bits of [`InputEvent::Key`] and [`InputEvent::PointerButton`].
+pub mod modifiers {
+    pub const ALT: u8 = 1;
+    pub const CTRL: u8 = 2;
+    pub const SHIFT: u8 = 4;
+    pub const META: u8 = 8;
+}
+
+/// input sent from browser to native app.
+///
+/// coordinates are in CSS pixels of the client canvas.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    /// canvas size and device pixel ratio.
+    Resize {
+        width: f32,
+        height: f32,
+        pixels_per_point: f32,
+    },
+    PointerMoved {
+        x: f32,
+        y: f32,
+    },
+    /// button follows `MouseEvent.button`. 0 primary, 1 middle, 2 secondary.
+    PointerButton {
+        x: f32,
+        y: f32,
+        button: u8,
+        pressed: bool,
+        modifiers: u8,
+    },
+    PointerGone,
+    Scroll {
+        dx: f32,
+        dy: f32,
+    },
+    /// key follows `KeyboardEvent.key`.
+    Key {
+        key: String,
+        pressed: bool,
+        modifiers: u8,
+    },
+    Text(String),
+    FocusChanged(bool),
+}
+
+/// decoded frame.
+pub struct Frame {
+    pub shapes: Vec<ClippedShape>,
+    pub textures_delta: TexturesDelta,
+}
+
+/// native side. encode frames into messages.
+///
+/// shapes identical to previous frame are sent as copy operation.
+#[derive(Default)]
+pub struct FrameEncoder {
+    previous: Vec<Vec<u8>>,
+    previous_index: HashMap<u64, usize>,
+}
+
+impl FrameEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// forget previous frame. next frame is sent in full.
+    ///
+    /// call this when new client connected.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+        self.previous_index.clear();
+    }
+    pub fn encode(&mut self, shapes: &[ClippedShape], textures_delta: &TexturesDelta) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.u8(PROTOCOL_VERSION);
+        w.u8(MSG_FRAME);
+        // textures
+        w.u32(textures_delta.set.len() as u32);
+        for (id, delta) in textures_delta.set.iter() {
+            w.texture_id(*id);
+            w.image_delta(delta);
+        }
+        w.u32(textures_delta.free.len() as u32);
+        for id in &textures_delta.free {
+            w.texture_id(*id);
+        }
+        // shapes
+        let current: Vec<Vec<u8>> = shapes
+            .iter()
+            .map(|shape| {
+                let mut shape_writer = Writer::default();
+                shape_writer.rect(shape.0);
+                shape_writer.shape(&shape.1);
+                shape_writer.buf
+            })
+            .collect();
+        let mut ops = Writer::default();
+        let mut op_count = 0u32;
+        // pending copy run (start in previous, length).
+        let mut run: Option<(usize, usize)> = None;
+        for blob in &current {
+            let found = self
+                .previous_index
+                .get(&hash_of(blob))
+                .copied()
+                .filter(|&i| self.previous[i] == *blob);
+            match (found, run) {
+                (Some(i), Some((start, len))) if start + len == i => run = Some((start, len + 1)),
+                (Some(i), _) => {
+                    if let Some((start, len)) = run {
+                        ops.copy_op(start, len);
+                        op_count += 1;
+                    }
+                    run = Some((i, 1));
+                }
+                (None, _) => {
+                    if let Some((start, len)) = run.take() {
+                        ops.copy_op(start, len);
+                        op_count += 1;
+                    }
+                    ops.u8(OP_NEW);
+                    ops.bytes(blob);
+                    op_count += 1;
+                }
+            }
+        }
+        if let Some((start, len)) = run {
+            ops.copy_op(start, len);
+            op_count += 1;
+        }
+        w.u32(op_count);
+        w.buf.extend_from_slice(&ops.buf);
+
+        self.previous_index = current
+            .iter()
+            .enumerate()
+            .map(|(i, blob)| (hash_of(blob), i))
+            .collect();
+        self.previous = current;
+        w.buf
+    }
+}
+
+fn hash_of(bytes: &[u8]) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// browser side. decode messages into frames.
+pub struct FrameDecoder {
+    previous: Vec<ClippedShape>,
+    max_shapes: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            max_shapes: MAX_SHAPES,
+        }
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// frames with more shapes, nested ones included, are malformed. 1 << 20 by default.
+    pub fn set_max_shapes(&mut self, max_shapes: usize) {
+        self.max_shapes = max_shapes;
+    }
+    /// returns `None` on malformed message or version mismatch.
+    pub fn decode(&mut self, message: &[u8]) -> Option<Frame> {
+        let mut r = Reader::new(message);
+        if r.u8()? != PROTOCOL_VERSION || r.u8()? != MSG_FRAME {
+            return None;
+        }
+        let mut textures_delta = TexturesDelta::default();
+        for _ in 0..r.count(9)? {
+            let id = r.texture_id()?;
+            let delta = r.image_delta()?;
+            textures_delta.set.insert(id, delta);
+        }
+        for _ in 0..r.count(9)? {
+            textures_delta.free.push(r.texture_id()?);
+        }
+        let mut shapes = Vec::with_capacity(self.previous.len());
+        let mut shape_count = 0;
+        for _ in 0..r.count(1)? {
+            match r.u8()? {
+                OP_COPY => {
+                    let start = r.u32()? as usize;
+                    let len = r.u32()? as usize;
+                    let end = start.checked_add(len)?;
+                    let copied = self.previous.get(start..end)?;
+                    for ClippedShape(_, shape) in copied {
+                        shape_count += count_shapes(shape);
+                    }
+                    if shape_count > self.max_shapes {
+                        return None;
+                    }
+                    shapes.extend_from_slice(copied);
+                }
+                OP_NEW => {
+                    let blob = r.bytes()?;
+                    let mut shape_reader = Reader::new(blob);
+                    let clip = shape_reader.rect()?;
+                    let shape = shape_reader.shape()?;
+                    shape_count += count_shapes(&shape);
+                    if shape_count > self.max_shapes {
+                        return None;
+                    }
+                    shapes.push(ClippedShape(clip, shape));
+                }
+                _ => return None,
+            }
+        }
+        self.previous = shapes.clone();
+        Some(Frame {
+            shapes,
+            textures_delta,
+        })
+    }
+}
+
+/// `shape` and shapes nested in it.
+fn count_shapes(shape: &Shape) -> usize {
+    match shape {
+        Shape::Vec(shapes) => 1 + shapes.iter().map(count_shapes).sum::<usize>(),
+        _ => 1,
+    }
+}
+
+pub fn encode_input(events: &[InputEvent]) -> Vec<u8> {
+    let mut w = Writer::default();
+    w.u8(PROTOCOL_VERSION);
+    w.u8(MSG_INPUT);
+    w.u32(events.len() as u32);
+    for event in events {
+        match event {
+            InputEvent::Resize {
+                width,
+                height,
+                pixels_per_point,
+            } => {
+                w.u8(0);
+                w.f32(*width);
+                w.f32(*height);
+                w.f32(*pixels_per_point);
+            }
+            InputEvent::PointerMoved { x, y } => {
+                w.u8(1);
+                w.f32(*x);
+                w.f32(*y);
+            }
+            InputEvent::PointerButton {
+                x,
+                y,
+                button,
+                pressed,
+                modifiers,
+            } => {
+                w.u8(2);
+                w.f32(*x);
+                w.f32(*y);
+                w.u8(*button);
+                w.u8(*pressed as u8);
+                w.u8(*modifiers);
+            }
+            InputEvent::PointerGone => w.u8(3),
+            InputEvent::Scroll { dx, dy } => {
+                w.u8(4);
+                w.f32(*dx);
+                w.f32(*dy);
+            }
+            InputEvent::Key {
+                key,
+                pressed,
+                modifiers,
+            } => {
+                w.u8(5);
+                w.str(key);
+                w.u8(*pressed as u8);
+                w.u8(*modifiers);
+            }
+            InputEvent::Text(text) => {
+                w.u8(6);
+                w.str(text);
+            }
+            InputEvent::FocusChanged(focused) => {
+                w.u8(7);
+                w.u8(*focused as u8);
+            }
+        }
+    }
+    w.buf
+}
+
+pub fn decode_input(message: &[u8]) -> Option<Vec<InputEvent>> {
+    let mut r = Reader::new(message);
+    if r.u8()? != PROTOCOL_VERSION || r.u8()? != MSG_INPUT {
+        return None;
+    }
+    let count = r.count(1)?;
+    let mut events = Vec::with_capacity(count);
+    for _ in 0..count {
+        let event = match r.u8()? {
+            0 => InputEvent::Resize {
+                width: r.f32()?,
+                height: r.f32()?,
+                pixels_per_point: r.f32()?,
+            },
+            1 => InputEvent::PointerMoved {
+                x: r.f32()?,
+                y: r.f32()?,
+            },
+            2 => InputEvent::PointerButton {
+                x: r.f32()?,
+                y: r.f32()?,
+                button: r.u8()?,
+                pressed: r.u8()? != 0,
+                modifiers: r.u8()?,
+            },
+            3 => InputEvent::PointerGone,
+            4 => InputEvent::Scroll {
+                dx: r.f32()?,
+                dy: r.f32()?,
+            },
+            5 => InputEvent::Key {
+                key: r.str()?,
+                pressed: r.u8()? != 0,
+                modifiers: r.u8()?,
+            },
+            6 => InputEvent::Text(r.str()?),
+            7 => InputEvent::FocusChanged(r.u8()? != 0),
+            _ => return None,
+        };
+        events.push(event);
+    }
+    Some(events)
+}
+
+#[derive(Default)]
+struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn f32(&mut self, v: f32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+    fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+    fn str(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+    fn pos(&mut self, p: Pos2) {
+        self.f32(p.x);
+        self.f32(p.y);
+    }
+    fn rect(&mut self, r: Rect) {
+        self.pos(r.min);
+        self.pos(r.max);
+    }
+    fn color(&mut self, c: Color32) {
+        self.buf.extend_from_slice(&c.to_array());
+    }
+    fn stroke(&mut self, s: Stroke) {
+        self.f32(s.width);
+        self.color(s.color);
+    }
+    fn copy_op(&mut self, start: usize, len: usize) {
+        self.u8(OP_COPY);
+        self.u32(start as u32);
+        self.u32(len as u32);
+    }
+    fn texture_id(&mut self, id: TextureId) {
+        match id {
+            TextureId::Managed(v) => {
+                self.u8(0);
+                self.u64(v);
+            }
+            TextureId::User(v) => {
+                self.u8(1);
+                self.u64(v);
+            }
+        }
+    }
+    fn image_delta(&mut self, delta: &ImageDelta) {
+        match delta.pos {
+            Some([x, y]) => {
+                self.u8(1);
+                self.u32(x as u32);
+                self.u32(y as u32);
+            }
+            None => self.u8(0),
+        }
+        let [w, h] = delta.image.size();
+        match &delta.image {
+            ImageData::Color(image) => {
+                self.u8(0);
+                self.u32(w as u32);
+                self.u32(h as u32);
+                image.pixels.iter().for_each(|p| self.color(*p));
+            }
+            ImageData::Alpha(image) => {
+                self.u8(1);
+                self.u32(w as u32);
+                self.u32(h as u32);
+                self.buf.extend_from_slice(&image.pixels);
+            }
+        }
+    }
+    fn mesh(&mut self, mesh: &Mesh) {
+        self.texture_id(mesh.texture_id);
+        self.u32(mesh.vertices.len() as u32);
+        for vertex in &mesh.vertices {
+            self.pos(vertex.pos);
+            self.pos(vertex.uv);
+            self.color(vertex.color);
+        }
+        self.u32(mesh.indices.len() as u32);
+        mesh.indices.iter().for_each(|i| self.u32(*i));
+    }
+    fn align(&mut self, align: Align) {
+        self.u8(match align {
+            Align::Min => 0,
+            Align::Center => 1,
+            Align::Max => 2,
+        });
+    }
+    fn job(&mut self, job: &LayoutJob) {
+        self.str(&job.text);
+        self.u32(job.sections.len() as u32);
+        for section in &job.sections {
+            self.f32(section.leading_space);
+            self.u32(section.byte_range.start as u32);
+            self.u32(section.byte_range.end as u32);
+            let format = &section.format;
+            self.f32(format.font_id.size);
+            match &format.font_id.family {
+                FontFamily::Proportional => self.u8(0),
+                FontFamily::Monospace => self.u8(1),
+                FontFamily::Name(name) => {
+                    self.u8(2);
+                    self.str(name);
+                }
+            }
+            self.color(format.color);
+            self.color(format.background);
+            self.u8(format.italics as u8);
+            self.stroke(format.underline);
+            self.stroke(format.strikethrough);
+            self.align(format.valign);
+        }
+        self.f32(job.wrap_width);
+        self.f32(job.first_row_min_height);
+        self.u8(job.break_on_newline as u8);
+        self.align(job.halign);
+        self.u8(job.justify as u8);
+    }
+    fn shape(&mut self, shape: &Shape) {
+        match shape {
+            Shape::Noop => self.u8(0),
+            Shape::Vec(shapes) => {
+                self.u8(1);
+                self.u32(shapes.len() as u32);
+                shapes.iter().for_each(|shape| self.shape(shape));
+            }
+            Shape::Circle(CircleShape {
+                center,
+                radius,
+                fill,
+                stroke,
+            }) => {
+                self.u8(2);
+                self.pos(*center);
+                self.f32(*radius);
+                self.color(*fill);
+                self.stroke(*stroke);
+            }
+            Shape::LineSegment { points, stroke } => {
+                self.u8(3);
+                self.pos(points[0]);
+                self.pos(points[1]);
+                self.stroke(*stroke);
+            }
+            Shape::Path(PathShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            }) => {
+                self.u8(4);
+                self.u32(points.len() as u32);
+                points.iter().for_each(|p| self.pos(*p));
+                self.u8(*closed as u8);
+                self.color(*fill);
+                self.stroke(*stroke);
+            }
+            Shape::Rect(RectShape {
+                rect,
+                rounding,
+                fill,
+                stroke,
+            }) => {
+                self.u8(5);
+                self.rect(*rect);
+                self.f32(rounding.nw);
+                self.f32(rounding.ne);
+                self.f32(rounding.sw);
+                self.f32(rounding.se);
+                self.color(*fill);
+                self.stroke(*stroke);
+            }
+            Shape::Text(TextShape {
+                pos,
+                galley,
+                underline,
+                override_text_color,
+                angle,
+            }) => {
+                self.u8(6);
+                self.pos(*pos);
+                self.stroke(*underline);
+                match override_text_color {
+                    Some(color) => {
+                        self.u8(1);
+                        self.color(*color);
+                    }
+                    None => self.u8(0),
+                }
+                self.f32(*angle);
+                self.job(&galley.job);
+                self.rect(galley.rect);
+                self.rect(galley.mesh_bounds);
+                self.u32(galley.rows.len() as u32);
+                for row in &galley.rows {
+                    self.rect(row.rect);
+                    self.u8(row.ends_with_newline as u8);
+                    self.mesh(&row.visuals.mesh);
+                    self.rect(row.visuals.mesh_bounds);
+                    self.u32(row.visuals.glyph_vertex_range.start as u32);
+                    self.u32(row.visuals.glyph_vertex_range.end as u32);
+                    self.u32(row.glyphs.len() as u32);
+                    for glyph in &row.glyphs {
+                        self.u32(glyph.chr as u32);
+                        self.pos(glyph.pos);
+                        self.f32(glyph.size.x);
+                        self.f32(glyph.size.y);
+                        self.f32(glyph.uv_rect.offset.x);
+                        self.f32(glyph.uv_rect.offset.y);
+                        self.f32(glyph.uv_rect.size.x);
+                        self.f32(glyph.uv_rect.size.y);
+                        self.buf
+                            .extend_from_slice(&glyph.uv_rect.min[0].to_le_bytes());
+                        self.buf
+                            .extend_from_slice(&glyph.uv_rect.min[1].to_le_bytes());
+                        self.buf
+                            .extend_from_slice(&glyph.uv_rect.max[0].to_le_bytes());
+                        self.buf
+                            .extend_from_slice(&glyph.uv_rect.max[1].to_le_bytes());
+                        self.u32(glyph.section_index);
+                    }
+                }
+            }
+            Shape::Mesh(mesh) => {
+                self.u8(7);
+                self.mesh(mesh);
+            }
+            Shape::QuadraticBezier(QuadraticBezierShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            }) => {
+                self.u8(8);
+                points.iter().for_each(|p| self.pos(*p));
+                self.u8(*closed as u8);
+                self.color(*fill);
+                self.stroke(*stroke);
+            }
+            Shape::CubicBezier(CubicBezierShape {
+                points,
+                closed,
+                fill,
+                stroke,
+            }) => {
+                self.u8(9);
+                points.iter().for_each(|p| self.pos(*p));
+                self.u8(*closed as u8);
+                self.color(*fill);
+                self.stroke(*stroke);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    /// `Shape::Vec` nesting of shape being read.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, depth: 0 }
+    }
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Some(head)
+    }
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+    /// element count, each element at least `min_size` bytes.
+    /// counts larger than rest of message are malformed, so nothing huge is allocated.
+    fn count(&mut self, min_size: usize) -> Option<usize> {
+        let count = self.u32()? as usize;
+        if count.checked_mul(min_size)? > self.buf.len() {
+            return None;
+        }
+        Some(count)
+    }
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+    fn str(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+    fn pos(&mut self) -> Option<Pos2> {
+        Some(Pos2::new(self.f32()?, self.f32()?))
+    }
+    fn rect(&mut self) -> Option<Rect> {
+        Some(Rect::from_min_max(self.pos()?, self.pos()?))
+    }
+    fn color(&mut self) -> Option<Color32> {
+        let c = self.take(4)?;
+        Some(Color32::from_rgba_premultiplied(c[0], c[1], c[2], c[3]))
+    }
+    fn stroke(&mut self) -> Option<Stroke> {
+        Some(Stroke {
+            width: self.f32()?,
+            color: self.color()?,
+        })
+    }
+    fn texture_id(&mut self) -> Option<TextureId> {
+        match self.u8()? {
+            0 => Some(TextureId::Managed(self.u64()?)),
+            1 => Some(TextureId::User(self.u64()?)),
+            _ => None,
+        }
+    }
+    fn image_delta(&mut self) -> Option<ImageDelta> {
+        let pos = match self.u8()? {
+            0 => None,
+            _ => Some([self.u32()? as usize, self.u32()? as usize]),
+        };
+        let kind = self.u8()?;
+        let size = [self.u32()? as usize, self.u32()? as usize];
+        let len = size[0].checked_mul(size[1])?;
+        let image = match kind {
+            0 => {
+                let pixels = self
+                    .take(len.checked_mul(4)?)?
+                    .chunks_exact(4)
+                    .map(|c| Color32::from_rgba_premultiplied(c[0], c[1], c[2], c[3]))
+                    .collect();
+                ImageData::Color(ColorImage { size, pixels })
+            }
+            1 => ImageData::Alpha(AlphaImage {
+                size,
+                pixels: self.take(len)?.to_vec(),
+            }),
+            _ => return None,
+        };
+        Some(ImageDelta { image, pos })
+    }
+    fn points<const N: usize>(&mut self) -> Option<[Pos2; N]> {
+        let mut points = [Pos2::ZERO; N];
+        for p in points.iter_mut() {
+            *p = self.pos()?;
+        }
+        Some(points)
+    }
+    fn mesh(&mut self) -> Option<Mesh> {
+        let texture_id = self.texture_id()?;
+        let vertex_count = self.count(20)?;
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            vertices.push(Vertex {
+                pos: self.pos()?,
+                uv: self.pos()?,
+                color: self.color()?,
+            });
+        }
+        let index_count = self.count(4)?;
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            indices.push(self.u32()?);
+        }
+        Some(Mesh {
+            indices,
+            vertices,
+            texture_id,
+        })
+    }
+    fn align(&mut self) -> Option<Align> {
+        match self.u8()? {
+            0 => Some(Align::Min),
+            1 => Some(Align::Center),
+            2 => Some(Align::Max),
+            _ => None,
+        }
+    }
+    fn job(&mut self) -> Option<LayoutJob> {
+        let text = self.str()?;
+        let section_count = self.count(43)?;
+        let mut sections = Vec::with_capacity(section_count);
+        for _ in 0..section_count {
+            let leading_space = self.f32()?;
+            let byte_range = self.u32()? as usize..self.u32()? as usize;
+            // range must be in text, on char boundaries.
+            text.get(byte_range.clone())?;
+            let size = self.f32()?;
+            let family = match self.u8()? {
+                0 => FontFamily::Proportional,
+                1 => FontFamily::Monospace,
+                2 => FontFamily::Name(self.str()?.into()),
+                _ => return None,
+            };
+            let format = TextFormat {
+                font_id: FontId { size, family },
+                color: self.color()?,
+                background: self.color()?,
+                italics: self.u8()? != 0,
+                underline: self.stroke()?,
+                strikethrough: self.stroke()?,
+                valign: self.align()?,
+            };
+            sections.push(LayoutSection {
+                leading_space,
+                byte_range,
+                format,
+            });
+        }
+        Some(LayoutJob {
+            text,
+            sections,
+            wrap_width: self.f32()?,
+            first_row_min_height: self.f32()?,
+            break_on_newline: self.u8()? != 0,
+            halign: self.align()?,
+            justify: self.u8()? != 0,
+        })
+    }
+    fn shape(&mut self) -> Option<Shape> {
+        let shape = match self.u8()? {
+            0 => Shape::Noop,
+            1 => {
+                if self.depth == MAX_DEPTH {
+                    return None;
+                }
+                self.depth += 1;
+                let len = self.count(1)?;
+                let mut shapes = Vec::with_capacity(len);
+                for _ in 0..len {
+                    shapes.push(self.shape()?);
+                }
+                self.depth -= 1;
+                Shape::Vec(shapes)
+            }
+            2 => Shape::Circle(CircleShape {
+                center: self.pos()?,
+                radius: self.f32()?,
+                fill: self.color()?,
+                stroke: self.stroke()?,
+            }),
+            3 => Shape::LineSegment {
+                points: self.points()?,
+                stroke: self.stroke()?,
+            },
+            4 => {
+                let len = self.count(8)?;
+                let mut points = Vec::with_capacity(len);
+                for _ in 0..len {
+                    points.push(self.pos()?);
+                }
+                Shape::Path(PathShape {
+                    points,
+                    closed: self.u8()? != 0,
+                    fill: self.color()?,
+                    stroke: self.stroke()?,
+                })
+            }
+            5 => Shape::Rect(RectShape {
+                rect: self.rect()?,
+                rounding: Rounding {
+                    nw: self.f32()?,
+                    ne: self.f32()?,
+                    sw: self.f32()?,
+                    se: self.f32()?,
+                },
+                fill: self.color()?,
+                stroke: self.stroke()?,
+            }),
+            6 => {
+                let pos = self.pos()?;
+                let underline = self.stroke()?;
+                let override_text_color = match self.u8()? {
+                    0 => None,
+                    _ => Some(self.color()?),
+                };
+                let angle = self.f32()?;
+                let job = self.job()?;
+                let rect = self.rect()?;
+                let mesh_bounds = self.rect()?;
+                let row_count = self.count(62)?;
+                let mut rows = Vec::with_capacity(row_count);
+                for _ in 0..row_count {
+                    let rect = self.rect()?;
+                    let ends_with_newline = self.u8()? != 0;
+                    let mesh = self.mesh()?;
+                    let visuals_bounds = self.rect()?;
+                    let glyph_vertex_range = self.u32()? as usize..self.u32()? as usize;
+                    if glyph_vertex_range.start > glyph_vertex_range.end
+                        || glyph_vertex_range.end > mesh.vertices.len()
+                    {
+                        return None;
+                    }
+                    let visuals = RowVisuals {
+                        mesh,
+                        mesh_bounds: visuals_bounds,
+                        glyph_vertex_range,
+                    };
+                    let glyph_count = self.count(48)?;
+                    let mut glyphs = Vec::with_capacity(glyph_count);
+                    for _ in 0..glyph_count {
+                        let chr = char::from_u32(self.u32()?)?;
+                        let pos = self.pos()?;
+                        let size = self.pos()?.to_vec2();
+                        // `UvRect` is not exported by epaint, so fill fields one by one.
+                        let mut glyph = Glyph {
+                            chr,
+                            pos,
+                            size,
+                            uv_rect: Default::default(),
+                            section_index: 0,
+                        };
+                        glyph.uv_rect.offset = self.pos()?.to_vec2();
+                        glyph.uv_rect.size = self.pos()?.to_vec2();
+                        glyph.uv_rect.min = [self.u16()?, self.u16()?];
+                        glyph.uv_rect.max = [self.u16()?, self.u16()?];
+                        glyph.section_index = self.u32()?;
+                        glyphs.push(glyph);
+                    }
+                    rows.push(Row {
+                        glyphs,
+                        rect,
+                        visuals,
+                        ends_with_newline,
+                    });
+                }
+                let num_vertices = rows.iter().map(|row| row.visuals.mesh.vertices.len()).sum();
+                let num_indices = rows.iter().map(|row| row.visuals.mesh.indices.len()).sum();
+                Shape::Text(TextShape {
+                    pos,
+                    galley: Arc::new(Galley {
+                        job: Arc::new(job),
+                        rows,
+                        rect,
+                        mesh_bounds,
+                        num_vertices,
+                        num_indices,
+                    }),
+                    underline,
+                    override_text_color,
+                    angle,
+                })
+            }
+            7 => Shape::Mesh(self.mesh()?),
+            8 => Shape::QuadraticBezier(QuadraticBezierShape {
+                points: self.points()?,
+                closed: self.u8()? != 0,
+                fill: self.color()?,
+                stroke: self.stroke()?,
+            }),
+            9 => Shape::CubicBezier(CubicBezierShape {
+                points: self.points()?,
+                closed: self.u8()? != 0,
+                fill: self.color()?,
+                stroke: self.stroke()?,
+            }),
+            _ => return None,
+        };
+        Some(shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::pos2;
+
+    fn sample_shapes() -> Vec<ClippedShape> {
+        let clip = Rect::from_min_max(pos2(0.0, 0.0), pos2(100.0, 100.0));
+        vec![
+            ClippedShape(
+                clip,
+                Shape::circle_filled(pos2(10.0, 10.0), 5.0, Color32::RED),
+            ),
+            ClippedShape(
+                clip,
+                Shape::line_segment(
+                    [pos2(0.0, 0.0), pos2(50.0, 50.0)],
+                    Stroke::new(1.0, Color32::WHITE),
+                ),
+            ),
+            ClippedShape(
+                clip,
+                Shape::rect_filled(
+                    Rect::from_min_max(pos2(5.0, 5.0), pos2(20.0, 20.0)),
+                    3.0,
+                    Color32::BLUE,
+                ),
+            ),
+        ]
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let shapes = sample_shapes();
+        let mut textures_delta = TexturesDelta::default();
+        textures_delta.set.insert(
+            TextureId::Managed(0),
+            ImageDelta::full(AlphaImage {
+                size: [2, 2],
+                pixels: vec![0, 64, 128, 255],
+            }),
+        );
+        textures_delta.free.push(TextureId::User(3));
+        let mut encoder = FrameEncoder::new();
+        let mut decoder = FrameDecoder::new();
+        let frame = decoder
+            .decode(&encoder.encode(&shapes, &textures_delta))
+            .unwrap();
+        assert_eq!(frame.shapes, shapes);
+        assert!(frame.textures_delta == textures_delta);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let fonts = epaint::text::Fonts::new(1.0, 2048, Default::default());
+        let mut job = LayoutJob::simple(
+            "remote\ntext".into(),
+            FontId::monospace(20.0),
+            Color32::YELLOW,
+            120.0,
+        );
+        job.append(
+            " more",
+            4.0,
+            TextFormat::simple(FontId::proportional(11.0), Color32::GREEN),
+        );
+        let shapes = vec![ClippedShape(
+            Rect::EVERYTHING,
+            Shape::galley(pos2(3.0, 4.0), fonts.layout_job(job)),
+        )];
+        let frame = FrameEncoder::new().encode(&shapes, &TexturesDelta::default());
+        // job, row meshes and their colors come through.
+        assert_eq!(FrameDecoder::new().decode(&frame).unwrap().shapes, shapes);
+    }
+
+    #[test]
+    fn unchanged_shapes_are_copied() {
+        let mut shapes = sample_shapes();
+        let mut encoder = FrameEncoder::new();
+        let mut decoder = FrameDecoder::new();
+        let first = encoder.encode(&shapes, &TexturesDelta::default());
+        decoder.decode(&first).unwrap();
+        shapes[1] = ClippedShape(shapes[1].0, Shape::Noop);
+        let second = encoder.encode(&shapes, &TexturesDelta::default());
+        assert!(second.len() < first.len());
+        assert_eq!(decoder.decode(&second).unwrap().shapes, shapes);
+    }
+
+    #[test]
+    fn input_round_trip() {
+        let events = vec![
+            InputEvent::Resize {
+                width: 640.0,
+                height: 480.0,
+                pixels_per_point: 2.0,
+            },
+            InputEvent::PointerButton {
+                x: 1.0,
+                y: 2.0,
+                button: 0,
+                pressed: true,
+                modifiers: modifiers::SHIFT,
+            },
+            InputEvent::Key {
+                key: "Enter".to_owned(),
+                pressed: false,
+                modifiers: 0,
+            },
+            InputEvent::Text("あ".to_owned()),
+        ];
+        assert_eq!(decode_input(&encode_input(&events)).unwrap(), events);
+    }
+
+    #[test]
+    fn truncated_messages_are_rejected() {
+        let mut textures_delta = TexturesDelta::default();
+        textures_delta.set.insert(
+            TextureId::Managed(0),
+            ImageDelta::full(ColorImage::new([2, 2], Color32::RED)),
+        );
+        let frame = FrameEncoder::new().encode(&sample_shapes(), &textures_delta);
+        for len in 0..frame.len() {
+            assert!(FrameDecoder::new().decode(&frame[..len]).is_none());
+        }
+        let input = encode_input(&[InputEvent::Text("abc".to_owned())]);
+        for len in 0..input.len() {
+            assert!(decode_input(&input[..len]).is_none());
+        }
+    }
+
+    #[test]
+    fn oversized_counts_are_rejected() {
+        let header = |msg: u8| vec![PROTOCOL_VERSION, msg];
+        // u32::MAX events in a few bytes.
+        let mut input = header(MSG_INPUT);
+        input.extend_from_slice(&u32::MAX.to_le_bytes());
+        input.push(3);
+        assert!(decode_input(&input).is_none());
+        // image of u32::MAX squared pixels.
+        let mut w = Writer {
+            buf: header(MSG_FRAME),
+        };
+        w.u32(1);
+        w.texture_id(TextureId::Managed(0));
+        w.u8(0);
+        w.u8(0);
+        w.u32(u32::MAX);
+        w.u32(u32::MAX);
+        assert!(FrameDecoder::new().decode(&w.buf).is_none());
+        // copy past end of previous frame.
+        let mut w = Writer {
+            buf: header(MSG_FRAME),
+        };
+        w.u32(0);
+        w.u32(0);
+        w.u32(1);
+        w.copy_op(u32::MAX as usize, 1);
+        assert!(FrameDecoder::new().decode(&w.buf).is_none());
+    }
+
+    #[test]
+    fn repeated_copies_are_capped() {
+        let mut decoder = FrameDecoder::new();
+        decoder.set_max_shapes(1000);
+        let mut encoder = FrameEncoder::new();
+        decoder
+            .decode(&encoder.encode(&sample_shapes(), &TexturesDelta::default()))
+            .unwrap();
+        // each frame copies all of previous one twice.
+        let mut len = sample_shapes().len();
+        loop {
+            let mut w = Writer {
+                buf: vec![PROTOCOL_VERSION, MSG_FRAME],
+            };
+            w.u32(0);
+            w.u32(0);
+            w.u32(2);
+            w.copy_op(0, len);
+            w.copy_op(0, len);
+            match decoder.decode(&w.buf) {
+                Some(frame) => len = frame.shapes.len(),
+                None => break,
+            }
+        }
+        assert!(len <= 1000 && len * 2 > 1000);
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let blob = |depth: usize| {
+            let mut w = Writer::default();
+            w.rect(Rect::EVERYTHING);
+            for _ in 0..depth {
+                w.u8(1);
+                w.u32(1);
+            }
+            w.u8(0);
+            w.buf
+        };
+        let frame = |depth: usize| {
+            let mut w = Writer::default();
+            w.u8(PROTOCOL_VERSION);
+            w.u8(MSG_FRAME);
+            w.u32(0);
+            w.u32(0);
+            w.u32(1);
+            w.u8(OP_NEW);
+            w.bytes(&blob(depth));
+            w.buf
+        };
+        assert!(FrameDecoder::new().decode(&frame(MAX_DEPTH)).is_some());
+        assert!(FrameDecoder::new().decode(&frame(1_000_000)).is_none());
+    }
+}
@@ -0,0 +1,522 @@
+//! run [`epi::App`] in browser without winit.
+//!
+//! DOM events of the canvas are translated into [`egui::RawInput`].
+//!
+//! ```ignore
+//! let canvas: HtmlCanvasElement = /* canvas in your page */;
+//! let runner = egui_canvas::runner::AppRunner::new(&canvas, Box::new(MyApp::default())).unwrap();
+//! egui_canvas::runner::start(runner);
+//! ```
+use crate::scheduler::FrameScheduler;
+use crate::Renderer;
+use egui::{Color32, CursorIcon, Event, Key, Modifiers, PointerButton, Pos2, RawInput, Rect};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{DataTransfer, HtmlCanvasElement, KeyboardEvent, MouseEvent, WheelEvent};
+
+/// input collected by DOM listeners between frames.
+#[derive(Default)]
+struct WebInput {
+    raw: RawInput,
+    /// canvas has keyboard focus.
+    has_focus: bool,
+}
+
+pub struct AppRunner {
+    renderer: Renderer,
+    canvas: HtmlCanvasElement,
+    egui_ctx: egui::Context,
+    app: Box<dyn epi::App>,
+    frame: epi::Frame,
+    input: Rc<RefCell<WebInput>>,
+    start_time: f64,
+    cursor: CursorIcon,
+    scheduler: Rc<FrameScheduler>,
+}
+
+impl AppRunner {
+    /// paint `app` into `canvas` and listen its input events.
+    pub fn new(
+        canvas: &HtmlCanvasElement,
+        mut app: Box<dyn epi::App>,
+    ) -> Option<Rc<RefCell<Self>>> {
+        let mut renderer = Renderer::new_with_canvas(canvas)?;
+        let scheduler = FrameScheduler::new();
+        {
+            // paint again once textures of last frame arrived.
+            let scheduler = Rc::downgrade(&scheduler);
+            renderer.set_upload_callback(move || {
+                if let Some(scheduler) = scheduler.upgrade() {
+                    scheduler.request_frame();
+                }
+            });
+        }
+        let egui_ctx = egui::Context::default();
+        let frame = epi::Frame::new(epi::backend::FrameData {
+            info: epi::IntegrationInfo {
+                name: "egui_canvas",
+                web_info: None,
+                prefer_dark_mode: None,
+                cpu_usage: None,
+                native_pixels_per_point: Some(renderer.dpr as f32),
+            },
+            output: Default::default(),
+            repaint_signal: scheduler.repaint_signal(),
+        });
+        app.setup(&egui_ctx, &frame, None);
+        let input = Rc::new(RefCell::new(WebInput::default()));
+        install_listeners(canvas, &input, &egui_ctx, &scheduler)?;
+        Some(Rc::new(RefCell::new(Self {
+            renderer,
+            canvas: canvas.clone(),
+            egui_ctx,
+            app,
+            frame,
+            input,
+            start_time: now_sec(),
+            cursor: CursorIcon::Default,
+            scheduler,
+        })))
+    }
+    pub fn egui_ctx(&self) -> &egui::Context {
+        &self.egui_ctx
+    }
+    pub fn renderer(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+    /// run another frame even when egui is idle.
+    pub fn request_repaint(&self) {
+        self.scheduler.request_frame();
+    }
+    /// run app once and paint the result.
+    ///
+    /// returns `needs_repaint` of egui.
+    pub fn run_frame(&mut self) -> bool {
+        let frame_start = now_sec();
+        let raw_input = self.take_raw_input();
+        let app = &mut self.app;
+        let frame = &self.frame;
+        let full_output = self.egui_ctx.run(raw_input, |ctx| app.update(ctx, frame));
+        let clear_color: Color32 = self.app.clear_color().into();
+        let (platform_output, needs_repaint) =
+            self.renderer.render_full_output(&clear_color, full_output);
+        self.handle_platform_output(platform_output);
+        self.frame.lock().info.cpu_usage = Some((now_sec() - frame_start) as f32);
+        needs_repaint
+    }
+    fn take_raw_input(&mut self) -> RawInput {
+        let rect = self.canvas.get_bounding_client_rect();
+        let dpr = web_sys::window()
+            .map(|win| win.device_pixel_ratio())
+            .unwrap_or(1.0);
+        // keep backing store in physical pixels, fewer with adaptive resolution
+        // or when over canvas limits.
+        // with back buffer, visible canvas follows when frame is presented.
+        let size = self.renderer.surface_size([rect.width(), rect.height()]);
+        self.renderer.resize_surface(size);
+        let mut input = self.input.borrow_mut();
+        let mut raw = std::mem::take(&mut input.raw);
+        raw.screen_rect = Some(Rect::from_min_size(
+            Pos2::ZERO,
+            egui::vec2(rect.width() as f32, rect.height() as f32),
+        ));
+        raw.pixels_per_point = Some(dpr as f32);
+        raw.time = Some(now_sec() - self.start_time);
+        raw
+    }
+    fn handle_platform_output(&mut self, platform_output: egui::PlatformOutput) {
+        let egui::PlatformOutput {
+            cursor_icon,
+            open_url,
+            copied_text,
+            ..
+        } = platform_output;
+        // not while user types into other parts of the page.
+        if !copied_text.is_empty() && self.input.borrow().has_focus {
+            write_clipboard(&copied_text);
+        }
+        if cursor_icon != self.cursor {
+            self.cursor = cursor_icon;
+            let _ = self
+                .canvas
+                .style()
+                .set_property("cursor", cursor_css(cursor_icon));
+        }
+        if let Some(egui::output::OpenUrl { url, new_tab }) = open_url {
+            let target = if new_tab { "_blank" } else { "_self" };
+            if let Some(win) = web_sys::window() {
+                let _ = win.open_with_url_and_target(&url, target);
+            }
+        }
+    }
+}
+
+/// start frame loop.
+///
+/// frames run on animation frames while egui needs repaint, on input
+/// and on `request_repaint` of `epi::Frame`. otherwise the loop sleeps.
+pub fn start(runner: Rc<RefCell<AppRunner>>) {
+    let scheduler = runner.borrow().scheduler.clone();
+    scheduler.set_frame(move || runner.borrow_mut().run_frame());
+    scheduler.request_frame();
+}
+
+fn now_sec() -> f64 {
+    web_sys::window()
+        .and_then(|win| win.performance())
+        .map(|performance| performance.now() / 1000.0)
+        .unwrap_or(0.0)
+}
+
+fn is_mac() -> bool {
+    web_sys::window()
+        .and_then(|win| win.navigator().platform().ok())
+        .map(|platform| platform.starts_with("Mac"))
+        .unwrap_or(false)
+}
+
+fn install_listeners(
+    canvas: &HtmlCanvasElement,
+    input: &Rc<RefCell<WebInput>>,
+    egui_ctx: &egui::Context,
+    scheduler: &Rc<FrameScheduler>,
+) -> Option<()> {
+    // canvas receives key events only when focusable.
+    canvas.set_tab_index(0);
+    let mac = is_mac();
+
+    // every event wakes up the frame loop.
+    let add = |name: &str, mut listener: Box<dyn FnMut(web_sys::Event)>| -> Option<()> {
+        let scheduler = scheduler.clone();
+        let closure = Closure::wrap(Box::new(move |event| {
+            listener(event);
+            scheduler.request_frame();
+        }) as Box<dyn FnMut(web_sys::Event)>);
+        canvas
+            .add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())
+            .ok()?;
+        closure.forget();
+        Some(())
+    };
+
+    {
+        let input = input.clone();
+        let canvas_c = canvas.clone();
+        add(
+            "mousemove",
+            Box::new(move |event| {
+                let event: MouseEvent = event.unchecked_into();
+                let pos = pointer_pos(&canvas_c, &event);
+                let mut input = input.borrow_mut();
+                input.raw.events.push(Event::PointerMoved(pos));
+            }),
+        )?;
+    }
+    for (name, pressed) in [("mousedown", true), ("mouseup", false)] {
+        let input = input.clone();
+        let canvas_c = canvas.clone();
+        add(
+            name,
+            Box::new(move |event| {
+                let event: MouseEvent = event.unchecked_into();
+                let button = match event.button() {
+                    0 => PointerButton::Primary,
+                    1 => PointerButton::Middle,
+                    2 => PointerButton::Secondary,
+                    _ => return,
+                };
+                if pressed {
+                    let _ = canvas_c.focus();
+                }
+                let pos = pointer_pos(&canvas_c, &event);
+                let modifiers = modifiers(
+                    event.alt_key(),
+                    event.ctrl_key(),
+                    event.shift_key(),
+                    event.meta_key(),
+                    mac,
+                );
+                let mut input = input.borrow_mut();
+                input.raw.modifiers = modifiers;
+                input.raw.events.push(Event::PointerButton {
+                    pos,
+                    button,
+                    pressed,
+                    modifiers,
+                });
+                event.prevent_default();
+            }),
+        )?;
+    }
+    {
+        let input = input.clone();
+        add(
+            "mouseleave",
+            Box::new(move |_| {
+                input.borrow_mut().raw.events.push(Event::PointerGone);
+            }),
+        )?;
+    }
+    add(
+        "contextmenu",
+        Box::new(|event| {
+            event.prevent_default();
+        }),
+    )?;
+    {
+        let input = input.clone();
+        let canvas_c = canvas.clone();
+        add(
+            "wheel",
+            Box::new(move |event| {
+                let event: WheelEvent = event.unchecked_into();
+                // delta_mode: 0 pixel, 1 line, 2 page.
+                let scale = match event.delta_mode() {
+                    WheelEvent::DOM_DELTA_LINE => 8.0,
+                    WheelEvent::DOM_DELTA_PAGE => canvas_c.client_height() as f32,
+                    _ => 1.0,
+                };
+                let mut delta = -scale * egui::vec2(event.delta_x() as f32, event.delta_y() as f32);
+                let mut input = input.borrow_mut();
+                if event.ctrl_key() || event.meta_key() {
+                    input.raw.events.push(Event::Zoom((delta.y / 200.0).exp()));
+                } else {
+                    if event.shift_key() {
+                        delta = egui::vec2(delta.x + delta.y, 0.0);
+                    }
+                    input.raw.events.push(Event::Scroll(delta));
+                }
+                event.prevent_default();
+            }),
+        )?;
+    }
+    for (name, pressed) in [("keydown", true), ("keyup", false)] {
+        let input = input.clone();
+        let egui_ctx = egui_ctx.clone();
+        add(
+            name,
+            Box::new(move |event| {
+                let event: KeyboardEvent = event.unchecked_into();
+                let modifiers = modifiers(
+                    event.alt_key(),
+                    event.ctrl_key(),
+                    event.shift_key(),
+                    event.meta_key(),
+                    mac,
+                );
+                let key_name = event.key();
+                let mut input = input.borrow_mut();
+                input.raw.modifiers = modifiers;
+                if let Some(key) = translate_key(&key_name) {
+                    if pressed && modifiers.command && key == Key::C {
+                        input.raw.events.push(Event::Copy);
+                    } else if pressed && modifiers.command && key == Key::X {
+                        input.raw.events.push(Event::Cut);
+                    }
+                    input.raw.events.push(Event::Key {
+                        key,
+                        pressed,
+                        modifiers,
+                    });
+                }
+                // printable character. browser reports it as single char key.
+                if pressed && key_name.chars().count() == 1 && !modifiers.ctrl && !modifiers.mac_cmd
+                {
+                    input.raw.events.push(Event::Text(key_name.clone()));
+                }
+                // keep browser shortcuts like ctrl+v (paste event) alive.
+                let browser_shortcut = modifiers.command && key_name == "v";
+                if egui_ctx.wants_keyboard_input() && !browser_shortcut {
+                    event.prevent_default();
+                }
+            }),
+        )?;
+    }
+    {
+        let input = input.clone();
+        add(
+            "paste",
+            Box::new(move |event| {
+                // `ClipboardEvent` needs web_sys_unstable_apis, read the field directly.
+                if let Some(text) = js_sys::Reflect::get(&event, &"clipboardData".into())
+                    .ok()
+                    .and_then(|data| data.dyn_into::<DataTransfer>().ok())
+                    .and_then(|data| data.get_data("text").ok())
+                {
+                    input.borrow_mut().raw.events.push(Event::Paste(text));
+                }
+                event.prevent_default();
+            }),
+        )?;
+    }
+    {
+        let input = input.clone();
+        let egui_ctx = egui_ctx.clone();
+        add(
+            "blur",
+            Box::new(move |_| {
+                // keys held while leaving are never released.
+                let mut input = input.borrow_mut();
+                input.has_focus = false;
+                input.raw.modifiers = Modifiers::default();
+                input.raw.events.push(Event::PointerGone);
+                // keys go elsewhere now, so no text cursor.
+                egui_ctx.memory().stop_text_input();
+            }),
+        )?;
+    }
+    {
+        let input = input.clone();
+        add(
+            "focus",
+            Box::new(move |_| {
+                input.borrow_mut().has_focus = true;
+            }),
+        )?;
+    }
+    {
+        // canvas size follows layout.
+        let scheduler = scheduler.clone();
+        let closure = Closure::wrap(Box::new(move || {
+            scheduler.request_frame();
+        }) as Box<dyn FnMut()>);
+        web_sys::window()?
+            .add_event_listener_with_callback("resize", closure.as_ref().unchecked_ref())
+            .ok()?;
+        closure.forget();
+    }
+    Some(())
+}
+
+/// `navigator.clipboard.writeText`. web-sys has it only with web_sys_unstable_apis.
+fn write_clipboard(text: &str) {
+    let clipboard = web_sys::window()
+        .and_then(|win| js_sys::Reflect::get(&win.navigator(), &"clipboard".into()).ok())
+        .filter(|clipboard| !clipboard.is_undefined());
+    let clipboard = match clipboard {
+        Some(clipboard) => clipboard,
+        // insecure context.
+        None => {
+            log::warn!("clipboard not available, copied text dropped");
+            return;
+        }
+    };
+    let write = js_sys::Reflect::get(&clipboard, &"writeText".into())
+        .ok()
+        .and_then(|write| write.dyn_into::<js_sys::Function>().ok());
+    if let Some(write) = write {
+        if let Err(err) = write.call1(&clipboard, &text.into()) {
+            log::warn!("failed to write clipboard: {:?}", err);
+        }
+    }
+}
+
+/// CSS pixel position relative to canvas, in points.
+fn pointer_pos(canvas: &HtmlCanvasElement, event: &MouseEvent) -> Pos2 {
+    let rect = canvas.get_bounding_client_rect();
+    Pos2::new(
+        (event.client_x() as f64 - rect.left()) as f32,
+        (event.client_y() as f64 - rect.top()) as f32,
+    )
+}
+
+fn modifiers(alt: bool, ctrl: bool, shift: bool, meta: bool, mac: bool) -> Modifiers {
+    Modifiers {
+        alt,
+        ctrl,
+        shift,
+        mac_cmd: mac && meta,
+        command: if mac { meta } else { ctrl },
+    }
+}
+
+/// `KeyboardEvent.key` to egui key.
+fn translate_key(key: &str) -> Option<Key> {
+    let key = match key {
+        "ArrowDown" => Key::ArrowDown,
+        "ArrowLeft" => Key::ArrowLeft,
+        "ArrowRight" => Key::ArrowRight,
+        "ArrowUp" => Key::ArrowUp,
+        "Esc" | "Escape" => Key::Escape,
+        "Tab" => Key::Tab,
+        "Backspace" => Key::Backspace,
+        "Enter" => Key::Enter,
+        " " | "Space" | "Spacebar" => Key::Space,
+        "Help" | "Insert" => Key::Insert,
+        "Delete" => Key::Delete,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "0" => Key::Num0,
+        "1" => Key::Num1,
+        "2" => Key::Num2,
+        "3" => Key::Num3,
+        "4" => Key::Num4,
+        "5" => Key::Num5,
+        "6" => Key::Num6,
+        "7" => Key::Num7,
+        "8" => Key::Num8,
+        "9" => Key::Num9,
+        "a" | "A" => Key::A,
+        "b" | "B" => Key::B,
+        "c" | "C" => Key::C,
+        "d" | "D" => Key::D,
+        "e" | "E" => Key::E,
+        "f" | "F" => Key::F,
+        "g" | "G" => Key::G,
+        "h" | "H" => Key::H,
+        "i" | "I" => Key::I,
+        "j" | "J" => Key::J,
+        "k" | "K" => Key::K,
+        "l" | "L" => Key::L,
+        "m" | "M" => Key::M,
+        "n" | "N" => Key::N,
+        "o" | "O" => Key::O,
+        "p" | "P" => Key::P,
+        "q" | "Q" => Key::Q,
+        "r" | "R" => Key::R,
+        "s" | "S" => Key::S,
+        "t" | "T" => Key::T,
+        "u" | "U" => Key::U,
+        "v" | "V" => Key::V,
+        "w" | "W" => Key::W,
+        "x" | "X" => Key::X,
+        "y" | "Y" => Key::Y,
+        "z" | "Z" => Key::Z,
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn cursor_css(cursor: CursorIcon) -> &'static str {
+    match cursor {
+        CursorIcon::Alias => "alias",
+        CursorIcon::AllScroll => "all-scroll",
+        CursorIcon::Cell => "cell",
+        CursorIcon::ContextMenu => "context-menu",
+        CursorIcon::Copy => "copy",
+        CursorIcon::Crosshair => "crosshair",
+        CursorIcon::Default => "default",
+        CursorIcon::Grab => "grab",
+        CursorIcon::Grabbing => "grabbing",
+        CursorIcon::Help => "help",
+        CursorIcon::Move => "move",
+        CursorIcon::NoDrop => "no-drop",
+        CursorIcon::None => "none",
+        CursorIcon::NotAllowed => "not-allowed",
+        CursorIcon::PointingHand => "pointer",
+        CursorIcon::Progress => "progress",
+        CursorIcon::ResizeHorizontal => "ew-resize",
+        CursorIcon::ResizeNeSw => "nesw-resize",
+        CursorIcon::ResizeNwSe => "nwse-resize",
+        CursorIcon::ResizeVertical => "ns-resize",
+        CursorIcon::Text => "text",
+        CursorIcon::VerticalText => "vertical-text",
+        CursorIcon::Wait => "wait",
+        CursorIcon::ZoomIn => "zoom-in",
+        CursorIcon::ZoomOut => "zoom-out",
+    }
+}
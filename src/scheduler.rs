@@ -0,0 +1,152 @@
+//! frame loop on top of `requestAnimationFrame`.
+//!
+//! frames run only when requested. any number of requests before next
+//! animation frame result in one frame. while idle nothing runs. other threads
+//! can't call into the DOM, so their `request_repaint` wakes the scheduler
+//! through a `BroadcastChannel` message.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+
+thread_local! {
+    /// live schedulers, so `RepaintSignal` (which must be `Send`) can find its scheduler.
+    static SCHEDULERS: RefCell<HashMap<u64, Weak<FrameScheduler>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+pub struct FrameScheduler {
+    id: u64,
+    /// animation frame already requested.
+    scheduled: Cell<bool>,
+    /// set by repaint signal. may come from other thread.
+    repaint_requested: Arc<AtomicBool>,
+    frame: RefCell<Option<Box<dyn FnMut() -> bool>>>,
+    callback: RefCell<Option<Closure<dyn FnMut()>>>,
+    /// channel other threads post to. unique name, as other tabs of same origin share channels.
+    channel_name: String,
+    channel: Option<web_sys::BroadcastChannel>,
+    on_message: RefCell<Option<Closure<dyn FnMut()>>>,
+}
+
+impl FrameScheduler {
+    pub fn new() -> Rc<Self> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let channel_name = format!(
+            "egui_canvas_repaint_{}_{}",
+            id,
+            (js_sys::Math::random() * u32::MAX as f64) as u32
+        );
+        let channel = web_sys::BroadcastChannel::new(&channel_name).ok();
+        let scheduler = Rc::new(Self {
+            id,
+            scheduled: Cell::new(false),
+            repaint_requested: Arc::new(AtomicBool::new(false)),
+            frame: RefCell::new(None),
+            callback: RefCell::new(None),
+            channel_name,
+            channel,
+            on_message: RefCell::new(None),
+        });
+        let weak = Rc::downgrade(&scheduler);
+        *scheduler.callback.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+            if let Some(scheduler) = weak.upgrade() {
+                scheduler.on_animation_frame();
+            }
+        }) as Box<dyn FnMut()>));
+        if let Some(channel) = &scheduler.channel {
+            let weak = Rc::downgrade(&scheduler);
+            let on_message = Closure::wrap(Box::new(move || {
+                if let Some(scheduler) = weak.upgrade() {
+                    scheduler.request_frame();
+                }
+            }) as Box<dyn FnMut()>);
+            channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+            *scheduler.on_message.borrow_mut() = Some(on_message);
+        }
+        SCHEDULERS.with(|schedulers| {
+            schedulers
+                .borrow_mut()
+                .insert(id, Rc::downgrade(&scheduler))
+        });
+        scheduler
+    }
+    /// set frame function. it returns whether another frame is needed.
+    pub fn set_frame(&self, frame: impl FnMut() -> bool + 'static) {
+        *self.frame.borrow_mut() = Some(Box::new(frame));
+    }
+    /// run frame function on next animation frame.
+    pub fn request_frame(&self) {
+        if self.scheduled.replace(true) {
+            return;
+        }
+        let requested = self.callback.borrow().as_ref().and_then(|callback| {
+            web_sys::window()?
+                .request_animation_frame(callback.as_ref().unchecked_ref())
+                .ok()
+        });
+        if requested.is_none() {
+            self.scheduled.set(false);
+        }
+    }
+    /// signal for `epi::Frame`. `request_repaint` wakes this scheduler.
+    pub fn repaint_signal(&self) -> Arc<dyn epi::backend::RepaintSignal> {
+        Arc::new(SchedulerSignal {
+            id: self.id,
+            repaint_requested: self.repaint_requested.clone(),
+            channel_name: self.channel_name.clone(),
+        })
+    }
+    fn on_animation_frame(&self) {
+        self.scheduled.set(false);
+        self.repaint_requested.store(false, Ordering::SeqCst);
+        let needs_repaint = match self.frame.borrow_mut().as_mut() {
+            Some(frame) => frame(),
+            None => false,
+        };
+        // request_repaint during frame is kept in the flag.
+        if needs_repaint || self.repaint_requested.load(Ordering::SeqCst) {
+            self.request_frame();
+        }
+    }
+}
+
+impl Drop for FrameScheduler {
+    fn drop(&mut self) {
+        let _ = SCHEDULERS.try_with(|schedulers| schedulers.borrow_mut().remove(&self.id));
+        if let Some(channel) = &self.channel {
+            channel.set_onmessage(None);
+            channel.close();
+        }
+    }
+}
+
+struct SchedulerSignal {
+    id: u64,
+    repaint_requested: Arc<AtomicBool>,
+    channel_name: String,
+}
+
+impl epi::backend::RepaintSignal for SchedulerSignal {
+    fn request_repaint(&self) {
+        let pending = self.repaint_requested.swap(true, Ordering::SeqCst);
+        let scheduler = SCHEDULERS
+            .try_with(|schedulers| schedulers.borrow().get(&self.id).and_then(Weak::upgrade))
+            .ok()
+            .flatten();
+        if let Some(scheduler) = scheduler {
+            scheduler.request_frame();
+        } else if !pending {
+            // off main thread there is no scheduler. message wakes it, once per pending request.
+            // message is delivered even though sending channel closes right away.
+            if let Ok(channel) = web_sys::BroadcastChannel::new(&self.channel_name) {
+                let _ = channel.post_message(&wasm_bindgen::JsValue::NULL);
+                channel.close();
+            }
+        }
+    }
+}
@@ -0,0 +1,441 @@
+//! svg export of a frame.
+//!
+//! same translation as [`Renderer`](crate::Renderer), written as svg elements.
+//! * path, arc and bezier -> `<path>`
+//! * clip rectangle -> `<clipPath>`
+//! * texture -> `<image>` with png data url
+//! * text -> glyph images ([`TextRenderingMode::EGUI`]) or `<text>` ([`TextRenderingMode::Browser`])
+use crate::canvas::{paint_clipped, Canvas2d};
+use crate::cpu_texture::{color_image_data_url, CpuTextures};
+use crate::TextRenderingMode;
+use epaint::{textures::TexturesDelta, ClippedShape, Color32, ImageDelta, Rect, TextureId, Vec2};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// keeps textures and writes frames as standalone svg documents.
+///
+/// feed `textures_delta` of every frame by [`SvgExporter::update_textures`]
+/// so that font and user textures are available when exporting.
+pub struct SvgExporter {
+    textures: CpuTextures,
+    rendering_mode: TextRenderingMode,
+}
+
+impl Default for SvgExporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvgExporter {
+    pub fn new() -> Self {
+        Self {
+            textures: CpuTextures::default(),
+            rendering_mode: TextRenderingMode::EGUI,
+        }
+    }
+    /// set text rendering mode
+    pub fn set_rendering_mode(&mut self, render_mode: TextRenderingMode) {
+        self.rendering_mode = render_mode;
+    }
+    /// apply texture delta of one frame.
+    ///
+    /// freed textures are kept until next call, so shapes of the same frame can still use them.
+    pub fn update_textures(&mut self, textures_delta: &TexturesDelta) {
+        self.textures.update(textures_delta);
+    }
+    pub fn set_texture(&mut self, id: TextureId, image_delta: &ImageDelta) {
+        self.textures.set(id, image_delta);
+    }
+    pub fn free_texture(&mut self, id: TextureId) {
+        self.textures.free(id);
+    }
+    /// write shapes as svg document of `size` points.
+    pub fn export(&self, shapes: &[ClippedShape], size: Vec2) -> String {
+        let mut canvas = SvgCanvas::new(&self.textures);
+        for shape in shapes {
+            paint_clipped(&mut canvas, self.rendering_mode, shape);
+        }
+        canvas.finish(size)
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    fill: Color32,
+    stroke: Color32,
+    line_width: f64,
+    font: String,
+    /// `<g>` opened by clip in this state.
+    open_groups: usize,
+}
+
+struct SvgCanvas<'a> {
+    textures: &'a CpuTextures,
+    used_textures: BTreeSet<TextureId>,
+    defs: String,
+    body: String,
+    path: String,
+    has_point: bool,
+    state: State,
+    stack: Vec<State>,
+    clip_count: usize,
+}
+
+impl<'a> SvgCanvas<'a> {
+    fn new(textures: &'a CpuTextures) -> Self {
+        Self {
+            textures,
+            used_textures: BTreeSet::new(),
+            defs: String::new(),
+            body: String::new(),
+            path: String::new(),
+            has_point: false,
+            state: State {
+                fill: Color32::BLACK,
+                stroke: Color32::BLACK,
+                line_width: 1.0,
+                font: "10px sans-serif".to_owned(),
+                open_groups: 0,
+            },
+            stack: vec![],
+            clip_count: 0,
+        }
+    }
+    fn finish(mut self, size: Vec2) -> String {
+        while !self.stack.is_empty() {
+            self.restore();
+        }
+        for _ in 0..self.state.open_groups {
+            self.body.push_str("</g>\n");
+        }
+        for id in &self.used_textures {
+            if let Some(image) = self.textures.get(id) {
+                writeln!(
+                    self.defs,
+                    r#"<image id="{}" width="{}" height="{}" xlink:href="{}"/>"#,
+                    texture_ref(id),
+                    image.size[0],
+                    image.size[1],
+                    color_image_data_url(image)
+                )
+                .unwrap();
+            }
+        }
+        format!(
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" "#,
+                r#"width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                "\n<defs>\n{defs}</defs>\n{body}</svg>\n"
+            ),
+            w = num(size.x as f64),
+            h = num(size.y as f64),
+            defs = self.defs,
+            body = self.body
+        )
+    }
+    fn point(&mut self, x: f64, y: f64) {
+        let command = if self.has_point { 'L' } else { 'M' };
+        write!(self.path, "{}{} {} ", command, num(x), num(y)).unwrap();
+        self.has_point = true;
+    }
+}
+
+impl Canvas2d for SvgCanvas<'_> {
+    fn begin_path(&mut self) {
+        self.path.clear();
+        self.has_point = false;
+    }
+    fn close_path(&mut self) {
+        self.path.push_str("Z ");
+    }
+    fn move_to(&mut self, x: f64, y: f64) {
+        write!(self.path, "M{} {} ", num(x), num(y)).unwrap();
+        self.has_point = true;
+    }
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.point(x, y);
+    }
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let at = |angle: f64| (x + radius * angle.cos(), y + radius * angle.sin());
+        let (sx, sy) = at(start_angle);
+        self.point(sx, sy);
+        if radius <= 0.0 {
+            return;
+        }
+        let r = num(radius);
+        let sweep = end_angle - start_angle;
+        if sweep >= 2.0 * std::f64::consts::PI {
+            // svg arc can't draw full circle. split into two halves.
+            let (mx, my) = at(start_angle + std::f64::consts::PI);
+            write!(self.path, "A{r} {r} 0 0 1 {} {} ", num(mx), num(my)).unwrap();
+            write!(self.path, "A{r} {r} 0 0 1 {} {} ", num(sx), num(sy)).unwrap();
+        } else {
+            let (ex, ey) = at(end_angle);
+            let large = (sweep > std::f64::consts::PI) as u8;
+            write!(self.path, "A{r} {r} 0 {large} 1 {} {} ", num(ex), num(ey)).unwrap();
+        }
+    }
+    fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        write!(
+            self.path,
+            "Q{} {} {} {} ",
+            num(cpx),
+            num(cpy),
+            num(x),
+            num(y)
+        )
+        .unwrap();
+    }
+    fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        write!(
+            self.path,
+            "C{} {} {} {} {} {} ",
+            num(cp1x),
+            num(cp1y),
+            num(cp2x),
+            num(cp2y),
+            num(x),
+            num(y)
+        )
+        .unwrap();
+    }
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64) {
+        write!(
+            self.path,
+            "M{} {} h{} v{} h{} Z ",
+            num(x),
+            num(y),
+            num(w),
+            num(h),
+            num(-w)
+        )
+        .unwrap();
+        self.has_point = true;
+    }
+    fn set_line_width(&mut self, width: f64) {
+        // canvas ignores zero, negative and non finite value.
+        if width > 0.0 && width.is_finite() {
+            self.state.line_width = width;
+        }
+    }
+    fn set_fill_color(&mut self, color: Color32) {
+        self.state.fill = color;
+    }
+    fn set_stroke_color(&mut self, color: Color32) {
+        self.state.stroke = color;
+    }
+    fn fill(&mut self) {
+        if self.path.is_empty() || self.state.fill.a() == 0 {
+            return;
+        }
+        writeln!(
+            self.body,
+            r#"<path d="{}" {}/>"#,
+            self.path.trim_end(),
+            paint_attributes("fill", self.state.fill)
+        )
+        .unwrap();
+    }
+    fn stroke(&mut self) {
+        if self.path.is_empty() || self.state.stroke.a() == 0 {
+            return;
+        }
+        writeln!(
+            self.body,
+            r#"<path d="{}" fill="none" {} stroke-width="{}"/>"#,
+            self.path.trim_end(),
+            paint_attributes("stroke", self.state.stroke),
+            num(self.state.line_width)
+        )
+        .unwrap();
+    }
+    fn save(&mut self) {
+        self.stack.push(self.state.clone());
+        self.state.open_groups = 0;
+    }
+    fn restore(&mut self) {
+        for _ in 0..self.state.open_groups {
+            self.body.push_str("</g>\n");
+        }
+        if let Some(state) = self.stack.pop() {
+            self.state = state;
+        } else {
+            self.state.open_groups = 0;
+        }
+    }
+    fn clip(&mut self) {
+        let id = self.clip_count;
+        self.clip_count += 1;
+        writeln!(
+            self.defs,
+            r#"<clipPath id="clip{}"><path d="{}"/></clipPath>"#,
+            id,
+            self.path.trim_end()
+        )
+        .unwrap();
+        writeln!(self.body, r#"<g clip-path="url(#clip{})">"#, id).unwrap();
+        self.state.open_groups += 1;
+    }
+    fn draw_texture(&mut self, id: TextureId, source: Rect, dest: Rect) {
+        if self.textures.get(&id).is_none() {
+            return;
+        }
+        self.used_textures.insert(id);
+        writeln!(
+            self.body,
+            concat!(
+                r#"<svg x="{}" y="{}" width="{}" height="{}" viewBox="{} {} {} {}" "#,
+                r##"preserveAspectRatio="none"><use xlink:href="#{}"/></svg>"##
+            ),
+            num(dest.min.x as f64),
+            num(dest.min.y as f64),
+            num(dest.width() as f64),
+            num(dest.height() as f64),
+            num(source.min.x as f64),
+            num(source.min.y as f64),
+            num(source.width() as f64),
+            num(source.height() as f64),
+            texture_ref(&id)
+        )
+        .unwrap();
+    }
+    fn set_font(&mut self, font: &str) {
+        self.state.font = font.to_owned();
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, _max_width: f64) {
+        if self.state.fill.a() == 0 {
+            return;
+        }
+        writeln!(
+            self.body,
+            r#"<text x="{}" y="{}" style="font: {}" {}>{}</text>"#,
+            num(x),
+            num(y),
+            escape(&self.state.font),
+            paint_attributes("fill", self.state.fill),
+            escape(text)
+        )
+        .unwrap();
+    }
+    fn texture_size(&self, id: TextureId) -> Option<[usize; 2]> {
+        self.textures.get(&id).map(|image| image.size)
+    }
+}
+
+/// `fill="rgb(..)" fill-opacity=".."` from premultiplied color.
+fn paint_attributes(name: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == 0xff {
+        format!(r#"{}="rgb({},{},{})""#, name, r, g, b)
+    } else {
+        format!(
+            r#"{name}="rgb({},{},{})" {name}-opacity="{}""#,
+            r,
+            g,
+            b,
+            num(a as f64 / 255.0)
+        )
+    }
+}
+
+fn texture_ref(id: &TextureId) -> String {
+    match id {
+        TextureId::Managed(id) => format!("texture-m{}", id),
+        TextureId::User(id) => format!("texture-u{}", id),
+    }
+}
+
+/// short number. 1/100 point is enough.
+fn num(v: f64) -> String {
+    let v = (v * 100.0).round() / 100.0;
+    if v == 0.0 {
+        // avoid "-0"
+        "0".to_owned()
+    } else {
+        format!("{}", v)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::{pos2, vec2, AlphaImage, Shape, Stroke};
+
+    fn clip() -> Rect {
+        Rect::from_min_max(pos2(0.0, 0.0), pos2(100.0, 100.0))
+    }
+
+    #[test]
+    fn shapes_become_elements() {
+        let shapes = vec![
+            ClippedShape(
+                clip(),
+                Shape::circle_filled(pos2(10.0, 10.0), 5.0, Color32::RED),
+            ),
+            ClippedShape(
+                clip(),
+                Shape::line_segment(
+                    [pos2(0.0, 0.0), pos2(50.0, 50.0)],
+                    Stroke::new(2.0, Color32::WHITE),
+                ),
+            ),
+        ];
+        let svg = SvgExporter::new().export(&shapes, vec2(100.0, 100.0));
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<clipPath").count(), 2);
+        assert_eq!(svg.matches("<g clip-path").count(), 2);
+        assert_eq!(svg.matches("</g>").count(), 2);
+        assert!(svg.contains(r#"fill="rgb(255,0,0)""#));
+        assert!(svg.contains(r#"stroke="rgb(255,255,255)" stroke-width="2""#));
+    }
+
+    #[test]
+    fn texture_is_embedded_once() {
+        let mut exporter = SvgExporter::new();
+        let mut textures_delta = TexturesDelta::default();
+        textures_delta.set.insert(
+            TextureId::Managed(0),
+            ImageDelta::full(AlphaImage {
+                size: [4, 4],
+                pixels: vec![255; 16],
+            }),
+        );
+        exporter.update_textures(&textures_delta);
+        let mut canvas = SvgCanvas::new(&exporter.textures);
+        for i in 0..3 {
+            canvas.draw_texture(
+                TextureId::Managed(0),
+                Rect::from_min_max(pos2(0.0, 0.0), pos2(2.0, 2.0)),
+                Rect::from_min_size(pos2(i as f32 * 10.0, 0.0), vec2(2.0, 2.0)),
+            );
+        }
+        let svg = canvas.finish(vec2(30.0, 10.0));
+        assert_eq!(svg.matches("data:image/png;base64,").count(), 1);
+        assert_eq!(svg.matches("<use").count(), 3);
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let textures = CpuTextures::default();
+        let mut canvas = SvgCanvas::new(&textures);
+        canvas.set_fill_color(Color32::WHITE);
+        canvas.fill_text("a<b & c", 0.0, 10.0, 100.0);
+        assert!(canvas.body.contains(">a&lt;b &amp; c</text>"));
+    }
+}
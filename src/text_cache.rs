@@ -0,0 +1,196 @@
+//! galleys painted once into offscreen canvases, then drawn with one `drawImage` per frame.
+use crate::texture::{canvas_bytes, create_context, release};
+use crate::TextRenderingMode;
+use epaint::text::Row;
+use epaint::TextShape;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+/// larger galleys are painted row by row, so culling still applies to them.
+const MAX_SIDE: u32 = 2048;
+
+struct CachedText {
+    context: CanvasRenderingContext2d,
+    last_used: u64,
+}
+
+/// bitmaps of galleys, least recently used evicted over budget.
+pub(crate) struct TextCache {
+    entries: HashMap<u64, CachedText>,
+    budget: usize,
+    bytes: usize,
+    tick: u64,
+}
+
+impl Default for TextCache {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            budget: 16 << 20,
+            bytes: 0,
+            tick: 0,
+        }
+    }
+}
+
+impl TextCache {
+    /// key of what `text` looks like. position is not part of it.
+    ///
+    /// built from rows, as job of a galley may not describe its glyphs. remote frames carry
+    /// text of job only.
+    pub fn key(text: &TextShape, rendering_mode: TextRenderingMode, dpr: f64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for row in &text.galley.rows {
+            hash_row(row, &mut hasher);
+        }
+        text.override_text_color.hash(&mut hasher);
+        text.underline.width.to_bits().hash(&mut hasher);
+        text.underline.color.hash(&mut hasher);
+        (rendering_mode == TextRenderingMode::EGUI).hash(&mut hasher);
+        dpr.to_bits().hash(&mut hasher);
+        hasher.finish()
+    }
+    /// bitmap of `key`, painted by `paint` on a miss. `None` when too large or caching is off.
+    pub fn get(
+        &mut self,
+        key: u64,
+        [width, height]: [u32; 2],
+        paint: impl FnOnce(&CanvasRenderingContext2d),
+    ) -> Option<HtmlCanvasElement> {
+        let bytes = width as usize * height as usize * 4;
+        if width > MAX_SIDE || height > MAX_SIDE || bytes > self.budget {
+            return None;
+        }
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.tick;
+            return entry.context.canvas();
+        }
+        self.shrink_to(self.budget - bytes);
+        let context = create_context(width, height);
+        paint(&context);
+        let canvas = context.canvas();
+        self.bytes += bytes;
+        self.entries.insert(
+            key,
+            CachedText {
+                context,
+                last_used: self.tick,
+            },
+        );
+        canvas
+    }
+    pub fn contains(&self, key: u64) -> bool {
+        self.entries.contains_key(&key)
+    }
+    /// 0 turns caching off.
+    pub fn set_budget(&mut self, bytes: usize) {
+        self.budget = bytes;
+        self.shrink_to(bytes);
+    }
+    /// font atlas changed, glyphs of cached bitmaps may be gone.
+    pub fn clear(&mut self) {
+        self.shrink_to(0);
+    }
+    fn shrink_to(&mut self, bytes: usize) {
+        while self.bytes > bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            let entry = match oldest.and_then(|key| self.entries.remove(&key)) {
+                Some(entry) => entry,
+                None => break,
+            };
+            self.bytes -= canvas_bytes(&entry.context);
+            release(&entry.context);
+        }
+    }
+}
+
+fn hash_row(row: &Row, hasher: &mut DefaultHasher) {
+    let rect = row.rect;
+    for value in [rect.min.x, rect.min.y, rect.max.x, rect.max.y] {
+        value.to_bits().hash(hasher);
+    }
+    row.glyphs.len().hash(hasher);
+    for glyph in &row.glyphs {
+        glyph.chr.hash(hasher);
+        let uv = &glyph.uv_rect;
+        for value in [
+            glyph.pos.x,
+            glyph.pos.y,
+            uv.offset.x,
+            uv.offset.y,
+            uv.size.x,
+            uv.size.y,
+        ] {
+            value.to_bits().hash(hasher);
+        }
+        uv.min.hash(hasher);
+        uv.max.hash(hasher);
+    }
+    for vertex in &row.visuals.mesh.vertices {
+        vertex.color.hash(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::text::LayoutJob;
+    use epaint::text::{FontDefinitions, Fonts};
+    use epaint::{pos2, Color32, FontId, Galley, Stroke};
+    use std::sync::Arc;
+
+    #[test]
+    fn key_ignores_position_only() {
+        let fonts = Fonts::new(1.0, 2048, FontDefinitions::default());
+        let galley = fonts.layout_no_wrap("cached".into(), FontId::default(), Color32::WHITE);
+        let text = TextShape {
+            pos: pos2(10.0, 20.0),
+            galley,
+            underline: Stroke::none(),
+            override_text_color: None,
+            angle: 0.0,
+        };
+        let key = |text: &TextShape| TextCache::key(text, TextRenderingMode::EGUI, 1.0);
+        let moved = TextShape {
+            pos: pos2(300.5, 7.0),
+            ..text.clone()
+        };
+        assert_eq!(key(&text), key(&moved));
+        let recolored = TextShape {
+            override_text_color: Some(Color32::RED),
+            ..text.clone()
+        };
+        assert_ne!(key(&text), key(&recolored));
+        assert_ne!(
+            key(&text),
+            TextCache::key(&text, TextRenderingMode::EGUI, 2.0)
+        );
+    }
+    #[test]
+    fn key_follows_glyphs_not_job() {
+        let fonts = Fonts::new(1.0, 2048, FontDefinitions::default());
+        // as remote frames carry them, with text only in job.
+        let text = |size: f32| {
+            let galley =
+                fonts.layout_no_wrap("cached".into(), FontId::proportional(size), Color32::WHITE);
+            let galley = Galley {
+                job: Arc::new(LayoutJob {
+                    text: "cached".into(),
+                    ..Default::default()
+                }),
+                ..(*galley).clone()
+            };
+            TextShape::new(pos2(0.0, 0.0), Arc::new(galley))
+        };
+        let key = |text: &TextShape| TextCache::key(text, TextRenderingMode::EGUI, 1.0);
+        assert_eq!(key(&text(14.0)), key(&text(14.0)));
+        assert_ne!(key(&text(14.0)), key(&text(20.0)));
+    }
+}
@@ -0,0 +1,817 @@
+//! texture canvases and how they are sampled.
+use epaint::{Shape, TextureId};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlImageElement, ImageBitmap};
+
+/// `imageSmoothingQuality` of canvas.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum SmoothingQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl SmoothingQuality {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            SmoothingQuality::Low => "low",
+            SmoothingQuality::Medium => "medium",
+            SmoothingQuality::High => "high",
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum TextureFilter {
+    /// no smoothing. pixel art and font atlas.
+    Nearest,
+    /// smoothing with given quality. photos.
+    Linear(SmoothingQuality),
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct TextureOptions {
+    pub filter: TextureFilter,
+    /// keep half, quarter, ... size copies and draw from the nearest one.
+    /// avoids aliasing of images drawn much smaller than their size.
+    pub mipmaps: bool,
+}
+
+impl TextureOptions {
+    pub const NEAREST: Self = Self {
+        filter: TextureFilter::Nearest,
+        mipmaps: false,
+    };
+    pub const LINEAR: Self = Self {
+        filter: TextureFilter::Linear(SmoothingQuality::Low),
+        mipmaps: false,
+    };
+    /// default of texture without options. nearest for font atlas, linear otherwise.
+    pub fn default_for(id: TextureId) -> Self {
+        if id == TextureId::default() {
+            Self::NEAREST
+        } else {
+            Self::LINEAR
+        }
+    }
+}
+
+/// set smoothing state of `context` for next `drawImage`.
+pub(crate) fn apply_filter(context: &CanvasRenderingContext2d, filter: TextureFilter) {
+    match filter {
+        TextureFilter::Nearest => context.set_image_smoothing_enabled(false),
+        TextureFilter::Linear(quality) => {
+            context.set_image_smoothing_enabled(true);
+            // web-sys has no binding for imageSmoothingQuality.
+            let _ = js_sys::Reflect::set(
+                context,
+                &"imageSmoothingQuality".into(),
+                &quality.as_str().into(),
+            );
+        }
+    }
+}
+
+/// memory used by texture canvases. canvas costs 4 bytes per pixel.
+#[derive(Eq, PartialEq, Copy, Clone, Debug, Default)]
+pub struct TextureMemory {
+    /// canvases of live textures, reduced copies included.
+    pub resident: usize,
+    /// free canvases kept for reuse.
+    pub pooled: usize,
+    /// evicted textures kept in RAM for re-upload. not canvas memory.
+    pub evicted: usize,
+    pub budget: Option<usize>,
+}
+
+/// texture uploaded to an offscreen canvas.
+pub(crate) struct WebTexture {
+    pub context: CanvasRenderingContext2d,
+    /// canvas of `context`. kept to skip `.canvas()` on each blit.
+    canvas: HtmlCanvasElement,
+    /// `ImageBitmap` copy of `context`, drawn instead of it when present.
+    pub snapshot: Rc<Snapshot>,
+    /// reduced copies. `levels[0]` is half size of `context`.
+    pub levels: Rc<RefCell<Vec<CanvasRenderingContext2d>>>,
+    /// rebuild `levels` after each upload.
+    pub mipmaps: Rc<Cell<bool>>,
+    /// uploads still decoding. canvas content is incomplete until none are pending.
+    pub uploads: Rc<Uploads>,
+    /// copies multiplied by tint colors.
+    pub tints: Rc<Tints>,
+    /// last frame which drew this texture.
+    pub last_used: u64,
+}
+
+impl WebTexture {
+    pub fn new(context: CanvasRenderingContext2d, mipmaps: bool) -> Self {
+        Self {
+            canvas: context.canvas().unwrap(),
+            context,
+            snapshot: Default::default(),
+            levels: Default::default(),
+            mipmaps: Rc::new(Cell::new(mipmaps)),
+            uploads: Default::default(),
+            tints: Default::default(),
+            last_used: 0,
+        }
+    }
+    pub fn size(&self) -> [u32; 2] {
+        [self.canvas.width(), self.canvas.height()]
+    }
+    pub fn bytes(&self) -> usize {
+        canvas_bytes(&self.context)
+            + self.levels.borrow().iter().map(canvas_bytes).sum::<usize>()
+            + self.tints.bytes()
+    }
+    /// give canvas back to `pool` and drop reduced copies.
+    ///
+    /// uploads still decoding are dropped when they land.
+    pub fn recycle(self, pool: &mut CanvasPool) {
+        self.uploads.recycle();
+        self.snapshot.invalidate();
+        self.tints.clear();
+        self.levels
+            .borrow_mut()
+            .drain(..)
+            .for_each(|level| release(&level));
+        pool.put(self.context);
+    }
+    /// canvas to draw `source` texels from, when it ends up `scale` times its size.
+    ///
+    /// returns the image and `source` in its texels.
+    pub fn select(&self, source: epaint::Rect, scale: f32) -> (TextureSource, epaint::Rect) {
+        let base = &self.canvas;
+        let levels = self.levels.borrow();
+        let mut level = 0;
+        let mut scale = scale;
+        while scale < 0.5 && level < levels.len() {
+            scale *= 2.0;
+            level += 1;
+        }
+        if level == 0 {
+            let image = match self.snapshot.get() {
+                Some(bitmap) => TextureSource::Bitmap(bitmap),
+                None => TextureSource::Canvas(base.clone()),
+            };
+            return (image, source);
+        }
+        let canvas = levels[level - 1].canvas().unwrap();
+        let factor = epaint::vec2(
+            canvas.width() as f32 / base.width() as f32,
+            canvas.height() as f32 / base.height() as f32,
+        );
+        let source = epaint::Rect::from_min_max(
+            (source.min.to_vec2() * factor).to_pos2(),
+            (source.max.to_vec2() * factor).to_pos2(),
+        );
+        (TextureSource::Canvas(canvas), source)
+    }
+    /// copy with texels multiplied by unmultiplied `rgb`.
+    pub fn tinted(&self, rgb: [u8; 3]) -> HtmlCanvasElement {
+        self.tints.get(&self.canvas, rgb)
+    }
+}
+
+/// tinted copies of a texture, most recently used first.
+#[derive(Default)]
+pub(crate) struct Tints {
+    copies: RefCell<Vec<([u8; 3], CanvasRenderingContext2d)>>,
+}
+
+impl Tints {
+    /// copies kept per texture. each is as large as the texture.
+    const MAX_COPIES: usize = 16;
+
+    fn get(&self, base: &HtmlCanvasElement, rgb: [u8; 3]) -> HtmlCanvasElement {
+        let mut copies = self.copies.borrow_mut();
+        let evicted = use_copy(&mut copies, rgb, Self::MAX_COPIES, || tint_copy(base, rgb));
+        for context in evicted {
+            release(&context);
+        }
+        copies[0].1.canvas().unwrap()
+    }
+    pub fn contains(&self, rgb: [u8; 3]) -> bool {
+        self.copies.borrow().iter().any(|(color, _)| *color == rgb)
+    }
+    /// texture changed.
+    pub fn clear(&self) {
+        for (_, context) in self.copies.borrow_mut().drain(..) {
+            release(&context);
+        }
+    }
+    pub fn bytes(&self) -> usize {
+        self.copies
+            .borrow()
+            .iter()
+            .map(|(_, context)| canvas_bytes(context))
+            .sum()
+    }
+}
+
+/// move copy of `rgb` to front of `copies`, made by `make` when missing.
+/// returns copies past `max`, least recently used.
+fn use_copy<T>(
+    copies: &mut Vec<([u8; 3], T)>,
+    rgb: [u8; 3],
+    max: usize,
+    make: impl FnOnce() -> T,
+) -> Vec<T> {
+    let copy = match copies.iter().position(|(color, _)| *color == rgb) {
+        Some(index) => copies.remove(index),
+        None => (rgb, make()),
+    };
+    copies.insert(0, copy);
+    copies
+        .drain(max.min(copies.len())..)
+        .map(|(_, copy)| copy)
+        .collect()
+}
+
+/// `base` multiplied by `rgb`, keeping alpha of `base`.
+fn tint_copy(base: &HtmlCanvasElement, rgb: [u8; 3]) -> CanvasRenderingContext2d {
+    let context = create_context(base.width(), base.height());
+    let (width, height) = (base.width() as f64, base.height() as f64);
+    context
+        .draw_image_with_html_canvas_element(base, 0.0, 0.0)
+        .unwrap();
+    context.set_global_composite_operation("multiply").unwrap();
+    context.set_fill_style(&format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]).into());
+    context.fill_rect(0.0, 0.0, width, height);
+    // multiply made transparent texels opaque. take alpha from base again.
+    context
+        .set_global_composite_operation("destination-in")
+        .unwrap();
+    context
+        .draw_image_with_html_canvas_element(base, 0.0, 0.0)
+        .unwrap();
+    context
+}
+
+/// texture larger than canvas limits, split into tiles which are within them.
+pub(crate) struct TiledTexture {
+    pub size: [u32; 2],
+    /// tiles row by row, with the texels each covers.
+    pub tiles: Vec<(epaint::Rect, WebTexture)>,
+}
+
+impl TiledTexture {
+    pub fn new(size: [u32; 2], tile_side: u32, pool: &mut CanvasPool, mipmaps: bool) -> Self {
+        let tiles = tile_rects(size, tile_side)
+            .into_iter()
+            .map(|rect| {
+                let context = pool.take(rect.width() as u32, rect.height() as u32);
+                (rect, WebTexture::new(context, mipmaps))
+            })
+            .collect();
+        Self { size, tiles }
+    }
+    pub fn bytes(&self) -> usize {
+        self.tiles.iter().map(|(_, tile)| tile.bytes()).sum()
+    }
+    pub fn pending(&self) -> bool {
+        self.tiles
+            .iter()
+            .any(|(_, tile)| tile.uploads.pending() > 0)
+    }
+    pub fn recycle(self, pool: &mut CanvasPool) {
+        for (_, tile) in self.tiles {
+            tile.recycle(pool);
+        }
+    }
+    /// parts of `source` on each tile: tile index, source in texels of the tile, and part of `dest`.
+    pub fn split(
+        &self,
+        source: epaint::Rect,
+        dest: epaint::Rect,
+    ) -> Vec<(usize, epaint::Rect, epaint::Rect)> {
+        split_source(self.tiles.iter().map(|(rect, _)| *rect), source, dest)
+    }
+}
+
+fn split_source(
+    tiles: impl Iterator<Item = epaint::Rect>,
+    source: epaint::Rect,
+    dest: epaint::Rect,
+) -> Vec<(usize, epaint::Rect, epaint::Rect)> {
+    let scale = dest.size() / source.size();
+    let to_dest = |texel: epaint::Pos2| dest.min + (texel - source.min) * scale;
+    tiles
+        .enumerate()
+        .filter_map(|(index, rect)| {
+            let part = source.intersect(rect);
+            if part.width() <= 0.0 || part.height() <= 0.0 {
+                return None;
+            }
+            let dest = epaint::Rect::from_min_max(to_dest(part.min), to_dest(part.max));
+            Some((index, part.translate(-rect.min.to_vec2()), dest))
+        })
+        .collect()
+}
+
+/// grid of squares of `side` covering `size`. last row and column may be smaller.
+fn tile_rects([width, height]: [u32; 2], side: u32) -> Vec<epaint::Rect> {
+    let mut rects = Vec::new();
+    for y in (0..height).step_by(side as usize) {
+        for x in (0..width).step_by(side as usize) {
+            rects.push(epaint::Rect::from_min_max(
+                epaint::pos2(x as f32, y as f32),
+                epaint::pos2((x + side).min(width) as f32, (y + side).min(height) as f32),
+            ));
+        }
+    }
+    rects
+}
+
+/// largest canvas the browser draws. larger ones fail silently, staying blank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CanvasLimits {
+    /// width or height in pixels.
+    pub max_side: u32,
+    /// width times height.
+    pub max_area: u64,
+}
+
+impl CanvasLimits {
+    /// limits of iOS Safari, lowest of common browsers.
+    pub const SAFE: Self = Self {
+        max_side: 4096,
+        max_area: 4096 * 4096,
+    };
+
+    /// area of other browsers. chrome and firefox take more, but not on every device.
+    const DESKTOP_AREA: u64 = 8192 * 8192;
+
+    /// try 1 pixel thin canvases of decreasing length for side. runs once per thread,
+    /// later calls return the same.
+    ///
+    /// area is known per platform, as probing it takes the memory it should protect.
+    pub fn probe() -> Self {
+        thread_local! {
+            static PROBED: Cell<Option<CanvasLimits>> = const { Cell::new(None) };
+        }
+        PROBED.with(|probed| {
+            let limits = probed.get().unwrap_or_else(|| {
+                let max_side = [32767, 16384, 8192]
+                    .into_iter()
+                    .find(|side| canvas_works(*side, 1) && canvas_works(1, *side))
+                    .unwrap_or(Self::SAFE.max_side);
+                let max_area = if is_ios() {
+                    Self::SAFE.max_area
+                } else {
+                    Self::DESKTOP_AREA
+                };
+                Self { max_side, max_area }
+            });
+            probed.set(Some(limits));
+            limits
+        })
+    }
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        width <= self.max_side
+            && height <= self.max_side
+            && width as u64 * height as u64 <= self.max_area
+    }
+    /// side of square tiles within limits.
+    pub fn tile_side(&self) -> u32 {
+        self.max_side.min((self.max_area as f64).sqrt() as u32)
+    }
+    /// `scale` lowered until surface of `css_size` times it fits.
+    pub fn clamp_scale(&self, [width, height]: [f64; 2], scale: f64) -> f64 {
+        let max_side = self.max_side as f64;
+        let mut scale = scale.min(max_side / width).min(max_side / height);
+        if width * height * scale * scale > self.max_area as f64 {
+            scale = (self.max_area as f64 / (width * height)).sqrt();
+        }
+        scale
+    }
+}
+
+/// iPhone, iPad, which reports itself as mac with touch, or iPod.
+fn is_ios() -> bool {
+    let navigator = match web_sys::window() {
+        Some(win) => win.navigator(),
+        None => return false,
+    };
+    let agent = navigator.user_agent().unwrap_or_default();
+    ["iPhone", "iPad", "iPod"]
+        .into_iter()
+        .any(|device| agent.contains(device))
+        || (agent.contains("Macintosh") && navigator.max_touch_points() > 1)
+}
+
+/// draw bottom right pixel of `width` x `height` canvas and read it back.
+/// canvas is released right after.
+fn canvas_works(width: u32, height: u32) -> bool {
+    let canvas: HtmlCanvasElement = match web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| doc.create_element("canvas").ok())
+        .and_then(|elem| elem.dyn_into().ok())
+    {
+        Some(canvas) => canvas,
+        None => return false,
+    };
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let pixel = canvas
+        .get_context("2d")
+        .ok()
+        .flatten()
+        .and_then(|context| context.dyn_into::<CanvasRenderingContext2d>().ok())
+        .and_then(|context| {
+            let (x, y) = (width as f64 - 1.0, height as f64 - 1.0);
+            context.fill_rect(x, y, 1.0, 1.0);
+            context.get_image_data(x, y, 1.0, 1.0).ok()
+        });
+    let works = matches!(pixel, Some(pixel) if pixel.data()[3] == 255);
+    canvas.set_width(0);
+    canvas.set_height(0);
+    works
+}
+
+/// uploads of one texture canvas.
+///
+/// canvas may go back to pool while its images still decode, and hold another texture
+/// when they land. those must not be drawn.
+#[derive(Default)]
+pub(crate) struct Uploads {
+    pending: Cell<u32>,
+    recycled: Cell<bool>,
+}
+
+impl Uploads {
+    pub fn pending(&self) -> u32 {
+        self.pending.get()
+    }
+    fn start(&self) {
+        self.pending.set(self.pending.get() + 1);
+    }
+    /// one upload landed. false when canvas was recycled and image is to be dropped.
+    fn finish(&self) -> bool {
+        self.pending.set(self.pending.get().saturating_sub(1));
+        !self.recycled.get()
+    }
+    fn recycle(&self) {
+        self.recycled.set(true);
+    }
+}
+
+/// canvas an upload is drawn into, with state to update when it lands.
+pub(crate) struct UploadTarget {
+    context: CanvasRenderingContext2d,
+    /// where image goes in texels of `context`.
+    pos: [f64; 2],
+    /// image replaces whole texture. canvas may be reused, its old pixels would show
+    /// through transparent parts.
+    replace: bool,
+    levels: Rc<RefCell<Vec<CanvasRenderingContext2d>>>,
+    mipmaps: Rc<Cell<bool>>,
+    uploads: Rc<Uploads>,
+    snapshot: Rc<Snapshot>,
+    tints: Rc<Tints>,
+}
+
+impl UploadTarget {
+    /// counts as pending upload of `texture` until [`Self::finish`].
+    pub fn new(texture: &WebTexture, pos: [f64; 2], replace: bool) -> Self {
+        texture.uploads.start();
+        texture.snapshot.invalidate();
+        Self {
+            context: texture.context.clone(),
+            pos,
+            replace,
+            levels: texture.levels.clone(),
+            mipmaps: texture.mipmaps.clone(),
+            uploads: texture.uploads.clone(),
+            snapshot: texture.snapshot.clone(),
+            tints: texture.tints.clone(),
+        }
+    }
+    /// draw decoded `image`, or only stop pending when decoding failed.
+    pub fn finish(&self, image: Option<&HtmlImageElement>, use_image_bitmaps: bool) {
+        if !self.uploads.finish() {
+            return;
+        }
+        if let Some(image) = image {
+            if self.replace {
+                let canvas = self.context.canvas().unwrap();
+                self.context
+                    .clear_rect(0.0, 0.0, canvas.width() as f64, canvas.height() as f64);
+            }
+            self.context
+                .draw_image_with_html_image_element(image, self.pos[0], self.pos[1])
+                .unwrap();
+            if self.mipmaps.get() {
+                *self.levels.borrow_mut() = build_levels(&self.context);
+            }
+            self.tints.clear();
+        }
+        if use_image_bitmaps && self.uploads.pending() == 0 {
+            self.snapshot.capture(&self.context.canvas().unwrap());
+        }
+    }
+}
+
+/// image given to `drawImage`.
+pub(crate) enum TextureSource {
+    Canvas(HtmlCanvasElement),
+    Bitmap(ImageBitmap),
+}
+
+/// `createImageBitmap` copy of a texture canvas.
+///
+/// creating is async. copy requested before the last invalidation is thrown away.
+#[derive(Default)]
+pub(crate) struct Snapshot {
+    bitmap: RefCell<Option<ImageBitmap>>,
+    generation: Cell<u64>,
+}
+
+impl Snapshot {
+    pub fn get(&self) -> Option<ImageBitmap> {
+        self.bitmap.borrow().clone()
+    }
+    /// texture changed. drop current copy and any copy in flight.
+    pub fn invalidate(&self) {
+        self.generation.set(self.generation.get() + 1);
+        if let Some(bitmap) = self.bitmap.borrow_mut().take() {
+            bitmap.close();
+        }
+    }
+    /// copy `canvas` into new bitmap.
+    pub fn capture(self: &Rc<Self>, canvas: &HtmlCanvasElement) {
+        self.invalidate();
+        let promise = match web_sys::window().and_then(|win| {
+            win.create_image_bitmap_with_html_canvas_element(canvas)
+                .ok()
+        }) {
+            Some(promise) => promise,
+            None => return,
+        };
+        let generation = self.generation.get();
+        let this = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let bitmap = wasm_bindgen_futures::JsFuture::from(promise).await;
+            if let Some(bitmap) = bitmap
+                .ok()
+                .and_then(|bitmap| bitmap.dyn_into::<ImageBitmap>().ok())
+            {
+                if this.generation.get() == generation {
+                    *this.bitmap.borrow_mut() = Some(bitmap);
+                } else {
+                    bitmap.close();
+                }
+            }
+        });
+    }
+}
+
+/// canvases of freed textures. creating canvases is slow and
+/// some browsers hold their memory long after they are dropped.
+#[derive(Default)]
+pub(crate) struct CanvasPool {
+    canvases: Vec<CanvasRenderingContext2d>,
+}
+
+impl CanvasPool {
+    /// canvas of given size. prefer one which already has it.
+    pub fn take(&mut self, width: u32, height: u32) -> CanvasRenderingContext2d {
+        let same_size = self.canvases.iter().position(|context| {
+            let canvas = context.canvas().unwrap();
+            canvas.width() == width && canvas.height() == height
+        });
+        match same_size.or_else(|| self.canvases.len().checked_sub(1)) {
+            Some(index) => {
+                let context = self.canvases.swap_remove(index);
+                let canvas = context.canvas().unwrap();
+                if canvas.width() == width && canvas.height() == height {
+                    context.clear_rect(0.0, 0.0, width as f64, height as f64);
+                } else {
+                    // resizing clears canvas.
+                    canvas.set_width(width);
+                    canvas.set_height(height);
+                }
+                context
+            }
+            None => create_context(width, height),
+        }
+    }
+    pub fn put(&mut self, context: CanvasRenderingContext2d) {
+        self.canvases.push(context);
+    }
+    pub fn bytes(&self) -> usize {
+        self.canvases.iter().map(canvas_bytes).sum()
+    }
+    /// release pooled canvases until pool is at most `bytes`.
+    pub fn shrink_to(&mut self, bytes: usize) {
+        while self.bytes() > bytes {
+            match self.canvases.pop() {
+                Some(context) => release(&context),
+                None => break,
+            }
+        }
+    }
+}
+
+pub(crate) fn canvas_bytes(context: &CanvasRenderingContext2d) -> usize {
+    let canvas = context.canvas().unwrap();
+    canvas.width() as usize * canvas.height() as usize * 4
+}
+
+/// zero size frees backing store right away, without waiting for GC.
+pub(crate) fn release(context: &CanvasRenderingContext2d) {
+    let canvas = context.canvas().unwrap();
+    canvas.set_width(0);
+    canvas.set_height(0);
+}
+
+/// `onload` handlers of images still decoding.
+///
+/// handler can't drop its own closure, so finished ones are dropped by [`Self::collect`].
+#[derive(Default)]
+pub(crate) struct UploadHandlers {
+    next_id: u64,
+    live: HashMap<u64, Closure<dyn FnMut()>>,
+    done: Rc<RefCell<Vec<u64>>>,
+}
+
+impl UploadHandlers {
+    /// call `handler` once `image` is decoded. its argument is false when decoding failed.
+    pub fn add(&mut self, image: &HtmlImageElement, mut handler: impl FnMut(bool) + 'static) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let done = self.done.clone();
+        let image_c = image.clone();
+        let closure = Closure::wrap(Box::new(move || {
+            image_c.set_onload(None);
+            image_c.set_onerror(None);
+            handler(image_c.natural_width() > 0);
+            done.borrow_mut().push(id);
+        }) as Box<dyn FnMut()>);
+        image.set_onload(Some(closure.as_ref().unchecked_ref()));
+        image.set_onerror(Some(closure.as_ref().unchecked_ref()));
+        self.live.insert(id, closure);
+    }
+    /// images still decoding.
+    pub fn pending(&self) -> usize {
+        self.live.len() - self.done.borrow().len()
+    }
+    /// drop handlers which already ran.
+    pub fn collect(&mut self) {
+        for id in self.done.borrow_mut().drain(..) {
+            self.live.remove(&id);
+        }
+    }
+}
+
+pub(crate) fn create_context(width: u32, height: u32) -> CanvasRenderingContext2d {
+    let canvas: HtmlCanvasElement = web_sys::window()
+        .unwrap()
+        .document()
+        .unwrap()
+        .create_element("canvas")
+        .unwrap()
+        .unchecked_into();
+    canvas.set_width(width);
+    canvas.set_height(height);
+    canvas
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<CanvasRenderingContext2d>()
+        .unwrap()
+}
+
+/// halve `base` until 1 texel wide and high.
+pub(crate) fn build_levels(base: &CanvasRenderingContext2d) -> Vec<CanvasRenderingContext2d> {
+    let mut levels: Vec<CanvasRenderingContext2d> = Vec::new();
+    let mut source = base.canvas().unwrap();
+    while source.width() > 1 || source.height() > 1 {
+        let (width, height) = ((source.width() / 2).max(1), (source.height() / 2).max(1));
+        let level = create_context(width, height);
+        apply_filter(&level, TextureFilter::Linear(SmoothingQuality::High));
+        level
+            .draw_image_with_html_canvas_element_and_dw_and_dh(
+                &source,
+                0.0,
+                0.0,
+                width as f64,
+                height as f64,
+            )
+            .unwrap();
+        source = level.canvas().unwrap();
+        levels.push(level);
+    }
+    levels
+}
+
+/// textures drawn by `shape`.
+pub(crate) fn collect_texture_ids(shape: &Shape, ids: &mut HashSet<TextureId>) {
+    match shape {
+        Shape::Vec(shapes) => shapes
+            .iter()
+            .for_each(|shape| collect_texture_ids(shape, ids)),
+        Shape::Text(_) => {
+            ids.insert(TextureId::default());
+        }
+        Shape::Mesh(mesh) => {
+            ids.insert(mesh.texture_id);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use epaint::{pos2, Rect};
+
+    #[test]
+    fn upload_after_recycle_is_dropped() {
+        let uploads = Uploads::default();
+        uploads.start();
+        uploads.start();
+        assert!(uploads.finish());
+        // texture freed while second image decodes.
+        uploads.recycle();
+        assert!(!uploads.finish());
+        assert_eq!(uploads.pending(), 0);
+    }
+
+    #[test]
+    fn tint_copies_are_reused_and_evicted() {
+        let mut copies = Vec::new();
+        let mut made = 0;
+        let mut make = || {
+            made += 1;
+            made
+        };
+        assert!(use_copy(&mut copies, [255, 0, 0], 2, &mut make).is_empty());
+        assert!(use_copy(&mut copies, [0, 255, 0], 2, &mut make).is_empty());
+        // red is cached, and used last now.
+        assert!(use_copy(&mut copies, [255, 0, 0], 2, &mut make).is_empty());
+        assert_eq!(copies[0], ([255, 0, 0], 1));
+        // blue pushes out green, least recently used.
+        assert_eq!(use_copy(&mut copies, [0, 0, 255], 2, &mut make), [2]);
+        assert_eq!(copies, [([0, 0, 255], 3), ([255, 0, 0], 1)]);
+    }
+
+    #[test]
+    fn tiles_cover_texture() {
+        let rects = tile_rects([5000, 3000], 2048);
+        assert_eq!(rects.len(), 6);
+        assert_eq!(
+            rects[2],
+            Rect::from_min_max(pos2(4096.0, 0.0), pos2(5000.0, 2048.0))
+        );
+        let area: f32 = rects.iter().map(|rect| rect.area()).sum();
+        assert_eq!(area, 5000.0 * 3000.0);
+    }
+
+    #[test]
+    fn source_is_split_at_tile_edges() {
+        let tiles = tile_rects([4000, 1000], 2048);
+        // texels 1948..2148 drawn twice as large.
+        let source = Rect::from_min_max(pos2(1948.0, 0.0), pos2(2148.0, 100.0));
+        let dest = Rect::from_min_max(pos2(0.0, 0.0), pos2(400.0, 200.0));
+        let parts = split_source(tiles.into_iter(), source, dest);
+        assert_eq!(parts.len(), 2);
+        let (index, part, part_dest) = parts[0];
+        assert_eq!(index, 0);
+        assert_eq!(
+            part,
+            Rect::from_min_max(pos2(1948.0, 0.0), pos2(2048.0, 100.0))
+        );
+        assert_eq!(
+            part_dest,
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(200.0, 200.0))
+        );
+        let (index, part, part_dest) = parts[1];
+        assert_eq!(index, 1);
+        assert_eq!(part, Rect::from_min_max(pos2(0.0, 0.0), pos2(100.0, 100.0)));
+        assert_eq!(
+            part_dest,
+            Rect::from_min_max(pos2(200.0, 0.0), pos2(400.0, 200.0))
+        );
+    }
+
+    #[test]
+    fn surface_scale_is_clamped() {
+        let limits = CanvasLimits::SAFE;
+        assert_eq!(limits.clamp_scale([1000.0, 800.0], 2.0), 2.0);
+        assert_eq!(limits.clamp_scale([3840.0, 100.0], 2.0), 4096.0 / 3840.0);
+        let scale = limits.clamp_scale([3000.0, 3000.0], 3.0);
+        assert!((3000.0 * scale).powi(2) <= limits.max_area as f64 + 1.0);
+        assert!(limits.fits(4096, 4096));
+        assert!(!limits.fits(4097, 16));
+        assert!(!CanvasLimits {
+            max_side: 8192,
+            max_area: 4096 * 4096
+        }
+        .fits(8192, 4096));
+    }
+}